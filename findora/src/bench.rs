@@ -0,0 +1,185 @@
+use crate::load::{KindStats, Mix, RunLimit};
+use feth::TestClient;
+use log::warn;
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+};
+use tokio::time::Instant;
+use web3::types::{Address, BlockId, BlockNumber, H256, U64};
+
+/// blocks to look back for tx hashes to query receipts
+const RECEIPT_BLOCKS: u64 = 100;
+/// max tx hashes to query receipts
+const RECEIPT_HASHES: usize = 1000;
+
+/// A read-only RPC method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Method {
+    /// `eth_getBalance`
+    Balance,
+    /// `eth_getTransactionCount`
+    Nonce,
+    /// `eth_getBlockByNumber`
+    Block,
+    /// `eth_getTransactionReceipt`
+    Receipt,
+    /// `eth_getCode` of the FRC20 contract
+    Code,
+    /// `eth_chainId`
+    ChainId,
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Method::Balance => "balance",
+            Method::Nonce => "nonce",
+            Method::Block => "block",
+            Method::Receipt => "receipt",
+            Method::Code => "code",
+            Method::ChainId => "chain_id",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "balance" => Ok(Method::Balance),
+            "nonce" => Ok(Method::Nonce),
+            "block" => Ok(Method::Block),
+            "receipt" => Ok(Method::Receipt),
+            "code" => Ok(Method::Code),
+            "chain_id" => Ok(Method::ChainId),
+            _ => Err(format!(
+                "Invalid method {}: balance, nonce, block, receipt, code and chain_id are supported",
+                s
+            )),
+        }
+    }
+}
+
+/// Parameters of the requests, collected before benchmarking
+#[derive(Debug, Default)]
+pub(crate) struct Samples {
+    /// the latest block height
+    pub height: u64,
+    /// hashes of recent txs
+    pub hashes: Vec<H256>,
+}
+
+impl Samples {
+    pub async fn collect(client: &TestClient) -> Option<Self> {
+        let height = client.block_number_async().await?.as_u64();
+        let mut hashes = vec![];
+        for n in (height.saturating_sub(RECEIPT_BLOCKS)..=height).rev() {
+            if hashes.len() >= RECEIPT_HASHES {
+                break;
+            }
+            let id = BlockId::Number(BlockNumber::Number(U64::from(n)));
+            if let Some(block) = client.block_with_tx_hashes_async(id).await {
+                hashes.extend(block.transactions);
+            }
+        }
+        hashes.truncate(RECEIPT_HASHES);
+        Some(Self { height, hashes })
+    }
+}
+
+/// send the `i`-th request of `method`, returns if it succeeded
+async fn request(client: &TestClient, method: Method, i: u64, samples: &Samples) -> bool {
+    let address = Address::from_low_u64_be(i + 1);
+    match method {
+        Method::Balance => client.try_balance_async(address, None).await.is_ok(),
+        Method::Nonce => client.nonce_async(address, None).await.is_some(),
+        Method::Block => {
+            let n = i % samples.height.max(1) + 1;
+            let id = BlockId::Number(BlockNumber::Number(U64::from(n)));
+            client.block_with_tx_hashes_async(id).await.is_some()
+        }
+        Method::Receipt => match samples.hashes.get(i as usize % samples.hashes.len().max(1)) {
+            Some(hash) => client.transaction_receipt_async(*hash).await.is_some(),
+            None => false,
+        },
+        Method::Code => client.frc20_code_async().await.is_some(),
+        Method::ChainId => client.chain_id_async().await.is_some(),
+    }
+}
+
+/// Send read requests of `mix` from `concurrency` workers, until `limit` is reached.
+///
+/// Workers are assigned to `clients` round-robin, every worker picks methods from the mix in turn.
+pub(crate) async fn bench_rpc(
+    clients: Arc<Vec<TestClient>>,
+    mix: Mix<Method>,
+    concurrency: usize,
+    limit: RunLimit,
+) -> (BTreeMap<Method, KindStats>, f64) {
+    let samples = Arc::new(Samples::collect(&clients[0]).await.unwrap_or_default());
+    let mix = if samples.hashes.is_empty() && mix.kinds.iter().any(|(m, _)| *m == Method::Receipt) {
+        warn!("no tx found in the latest {} blocks, receipt skipped", RECEIPT_BLOCKS);
+        Mix {
+            kinds: mix.kinds.into_iter().filter(|(m, _)| *m != Method::Receipt).collect(),
+        }
+    } else {
+        mix
+    };
+    if mix.kinds.is_empty() {
+        return (BTreeMap::new(), 0.0);
+    }
+    let mix = Arc::new(mix);
+    let sent = Arc::new(AtomicU64::new(0));
+
+    let start = Instant::now();
+    let workers = (0..concurrency)
+        .map(|w| {
+            let clients = clients.clone();
+            let mix = mix.clone();
+            let samples = samples.clone();
+            let sent = sent.clone();
+            tokio::spawn(async move {
+                let client = clients.get(w % clients.len()).unwrap();
+                let mut picker = vec![];
+                let mut stats: BTreeMap<Method, KindStats> = BTreeMap::new();
+                loop {
+                    let i = sent.fetch_add(1, Relaxed);
+                    match limit {
+                        RunLimit::Count(count) if i >= count => break,
+                        RunLimit::Duration(d) if start.elapsed() >= d => break,
+                        _ => {}
+                    }
+                    let method = mix.pick(&mut picker);
+                    let begin = Instant::now();
+                    let ok = request(client, method, i, &samples).await;
+                    let s = stats.entry(method).or_default();
+                    s.total += 1;
+                    if ok {
+                        s.succeed += 1;
+                        s.latency.saturating_record(begin.elapsed().as_millis() as u64);
+                    }
+                }
+                stats
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut total: BTreeMap<Method, KindStats> = BTreeMap::new();
+    for worker in workers {
+        worker.await.unwrap().into_iter().for_each(|(method, stats)| {
+            let all = total.entry(method).or_default();
+            all.total += stats.total;
+            all.succeed += stats.succeed;
+            all.latency.add(&stats.latency).unwrap();
+        });
+    }
+    (total, start.elapsed().as_secs_f64())
+}
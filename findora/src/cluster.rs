@@ -0,0 +1,406 @@
+use crate::{
+    load::{error_counts, error_samples, round_loop, transfer_metrics, RunLimit, Sender},
+    prepare_sources, setup_senders, target_count,
+};
+use feth::{
+    keys::KeyFile,
+    latency::{LatencySummary, LatencyTracker},
+    report::{ErrorSample, RunResult},
+    utils::real_network,
+    TestClient, TransferMetrics, TxFee,
+};
+use futures::future::{join_all, try_join_all};
+use hdrhistogram::{
+    serialization::{Deserializer, Serializer, V2Serializer},
+    Histogram,
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, io, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    runtime::Runtime,
+};
+use web3::types::{U256, U64};
+
+/// longest wait for workers to prepare their shards
+const READY_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A shard of a distributed test, handed to a worker by the coordinator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Job {
+    /// the network as given to the coordinator, endpoints are comma separated
+    pub network: String,
+    pub timeout: u64,
+    pub block_time: u64,
+    /// a range of a key set, derived by the worker from its own copy of the mnemonic, or the private
+    /// keys themselves
+    pub keys: KeyFile,
+    pub count: u64,
+    pub pipeline: u64,
+    pub delay: u64,
+    pub max_in_flight: usize,
+    pub duration: Option<u64>,
+    pub check_balance: bool,
+    pub fee: TxFee,
+}
+
+impl Job {
+    /// the longest a worker may take from the start to its report, twice the planned time of the
+    /// rounds, plus the wait for inclusion and a request timeout
+    fn run_time(&self) -> Duration {
+        let rounds = match self.duration {
+            Some(secs) => secs,
+            None => self.count.div_ceil(self.pipeline.max(1)) * (self.block_time + self.delay),
+        };
+        Duration::from_secs(rounds * 2 + self.block_time * 3 + 1 + self.timeout)
+    }
+}
+
+/// What a worker sends back once its rounds are done
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct WorkerReport {
+    pub total: u64,
+    pub succeed: u64,
+    pub elapsed_secs: f64,
+    pub end_height: u64,
+    /// latency histograms by name, V2 serialized and hex encoded
+    pub latency: BTreeMap<String, String>,
+    /// failed sends by error category
+    pub errors: BTreeMap<String, u64>,
+    pub error_samples: Vec<ErrorSample>,
+    pub metrics: Vec<TransferMetrics>,
+}
+
+/// Messages between the coordinator and a worker, one JSON object per line.
+///
+/// The coordinator sends a `Job` to every worker and waits until all of them are `Ready`, then it
+/// sends the same `Start` height to all of them. A round starts on a new block, so the rounds of
+/// all workers begin together after that height. Every worker answers `Done` or `Failed` at last.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Message {
+    Job(Job),
+    /// the shard is prepared, `keys` have enough balance
+    Ready {
+        keys: usize,
+        height: u64,
+    },
+    Start {
+        height: u64,
+    },
+    Done(WorkerReport),
+    Failed {
+        reason: String,
+    },
+}
+
+/// A connection speaking `Message`s
+struct Peer {
+    addr: SocketAddr,
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Peer {
+    fn new(stream: TcpStream, addr: SocketAddr) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self {
+            addr,
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+
+    async fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(Self::new(stream, addr))
+    }
+
+    async fn send(&mut self, message: &Message) -> io::Result<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await
+    }
+
+    async fn recv(&mut self) -> io::Result<Message> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+/// Split `keys` into contiguous shards of `job` for `workers`, run them in sync and collect the reports.
+///
+/// Workers are left out if there are fewer keys than workers.
+pub(crate) fn coordinate(
+    workers: &[SocketAddr],
+    job: Job,
+    keys: KeyFile,
+) -> Result<Vec<(SocketAddr, WorkerReport)>, String> {
+    let rt = Runtime::new().map_err(|e| e.to_string())?;
+    rt.block_on(async {
+        let shards = keys.split(workers.len());
+        let mut peers = try_join_all(workers.iter().take(shards.len()).map(|addr| Peer::connect(*addr)))
+            .await
+            .map_err(|e| format!("failed to connect workers: {}", e))?;
+
+        for (peer, keys) in peers.iter_mut().zip(shards) {
+            info!("sending {} keys to worker {}", keys.len(), peer.addr);
+            let job = Job { keys, ..job.clone() };
+            peer.send(&Message::Job(job))
+                .await
+                .map_err(|e| format!("worker {}: {}", peer.addr, e))?;
+        }
+
+        let mut height = 0u64;
+        for (peer, ready) in join_all(peers.iter_mut().map(|peer| async move {
+            let ready = recv_within(peer, READY_TIMEOUT).await;
+            (peer.addr, ready)
+        }))
+        .await
+        {
+            match ready {
+                Ok(Message::Ready { keys, height: h }) => {
+                    info!("worker {} ready with {} keys at height {}", peer, keys, h);
+                    height = height.max(h);
+                }
+                Ok(Message::Failed { reason }) => return Err(format!("worker {} failed: {}", peer, reason)),
+                Ok(other) => return Err(format!("worker {} sent {:?}", peer, other)),
+                Err(e) => return Err(format!("worker {}: {}", peer, e)),
+            }
+        }
+
+        info!("starting rounds of {} workers after height {}", peers.len(), height);
+        for peer in peers.iter_mut() {
+            peer.send(&Message::Start { height })
+                .await
+                .map_err(|e| format!("worker {}: {}", peer.addr, e))?;
+        }
+
+        let run_time = job.run_time();
+        let reports = join_all(peers.iter_mut().map(|peer| async move {
+            let done = recv_within(peer, run_time).await;
+            (peer.addr, done)
+        }))
+        .await;
+        let mut done = vec![];
+        for (peer, report) in reports {
+            match report {
+                Ok(Message::Done(report)) => done.push((peer, report)),
+                Ok(Message::Failed { reason }) => error!("worker {} failed: {}", peer, reason),
+                Ok(other) => error!("worker {} sent {:?}", peer, other),
+                Err(e) => error!("worker {}: {}", peer, e),
+            }
+        }
+        if done.is_empty() {
+            return Err("no worker finished its job".to_string());
+        }
+        Ok(done)
+    })
+}
+
+/// the next message of `peer`, an error if none comes in `timeout`
+async fn recv_within(peer: &mut Peer, timeout: Duration) -> io::Result<Message> {
+    tokio::time::timeout(timeout, peer.recv())
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("no message in {:?}", timeout)))?
+}
+
+/// Merge the reports of workers, latency histograms are added up before the percentiles are taken
+pub(crate) fn merge(reports: &[(SocketAddr, WorkerReport)], start_height: u64) -> RunResult {
+    let mut histograms: BTreeMap<String, Histogram<u64>> = BTreeMap::new();
+    reports.iter().for_each(|(_, report)| {
+        report.latency.iter().for_each(|(name, encoded)| match decode(encoded) {
+            Some(h) => match histograms.get_mut(name) {
+                Some(merged) => {
+                    if let Err(e) = merged.add(&h) {
+                        warn!("failed to merge {} latency: {:?}", name, e);
+                    }
+                }
+                None => {
+                    histograms.insert(name.clone(), h);
+                }
+            },
+            None => warn!("invalid {} latency histogram", name),
+        })
+    });
+    let mut errors = BTreeMap::new();
+    reports
+        .iter()
+        .flat_map(|(_, r)| r.errors.iter())
+        .for_each(|(category, count)| {
+            *errors.entry(category.clone()).or_default() += count;
+        });
+    RunResult {
+        total: reports.iter().map(|(_, r)| r.total).sum(),
+        succeed: reports.iter().map(|(_, r)| r.succeed).sum(),
+        elapsed_secs: reports.iter().map(|(_, r)| r.elapsed_secs).fold(0.0, f64::max),
+        start_height,
+        end_height: reports.iter().map(|(_, r)| r.end_height).max().unwrap_or(start_height),
+        latency: histograms
+            .iter()
+            .map(|(name, h)| (name.clone(), LatencySummary::from(h)))
+            .collect(),
+        errors,
+        ..Default::default()
+    }
+}
+
+/// distinct error messages of all workers, the most frequent first
+pub(crate) fn merge_errors(reports: &[(SocketAddr, WorkerReport)]) -> Vec<ErrorSample> {
+    let mut samples: BTreeMap<String, ErrorSample> = BTreeMap::new();
+    reports
+        .iter()
+        .flat_map(|(_, r)| r.error_samples.iter())
+        .for_each(|sample| {
+            samples
+                .entry(sample.message.clone())
+                .and_modify(|s| s.count += sample.count)
+                .or_insert_with(|| sample.clone());
+        });
+    let mut samples = samples.into_values().collect::<Vec<_>>();
+    samples.sort_by_key(|s| std::cmp::Reverse(s.count));
+    samples
+}
+
+/// Run the jobs of coordinators on `listen`, one at a time, forever. The keys of key sets are
+/// derived from the mnemonic in `seed` if given, instead of the file named in the job.
+pub(crate) fn work(listen: SocketAddr, seed: Option<PathBuf>) {
+    let rt = Runtime::new().unwrap();
+    let listener = rt.block_on(TcpListener::bind(listen)).unwrap();
+    info!("waiting for jobs on {}", listen);
+    loop {
+        let (stream, addr) = match rt.block_on(listener.accept()) {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("failed to accept a coordinator: {}", e);
+                continue;
+            }
+        };
+        info!("coordinator {} connected", addr);
+        let mut peer = Peer::new(stream, addr);
+        let reply = match run_job(&rt, &mut peer, seed.clone()) {
+            Ok(report) => Message::Done(report),
+            Err(reason) => {
+                error!("job of {} failed: {}", addr, reason);
+                Message::Failed { reason }
+            }
+        };
+        if let Err(e) = rt.block_on(peer.send(&reply)) {
+            error!("failed to reply to {}: {}", addr, e);
+        }
+    }
+}
+
+/// prepare the shard of `peer`, wait for the start and run the rounds
+fn run_job(rt: &Runtime, peer: &mut Peer, seed: Option<PathBuf>) -> Result<WorkerReport, String> {
+    let job = match rt.block_on(peer.recv()).map_err(|e| e.to_string())? {
+        Message::Job(job) => job,
+        other => return Err(format!("expect a job, got {:?}", other)),
+    };
+    info!("got {} keys, {} txs per key", job.keys.len(), job.count);
+    let keys = match (job.keys.clone(), seed) {
+        (KeyFile::Set(mut set), Some(seed)) => {
+            set.seed = seed;
+            KeyFile::Set(set)
+        }
+        (keys, _) => keys,
+    };
+    let keys = keys.keys().map_err(|e| format!("failed to load keys: {}", e))?;
+
+    let endpoints = real_network(&job.network);
+    if endpoints.is_empty() {
+        return Err(format!("no valid endpoint in {}", job.network));
+    }
+    let timeout = Some(job.timeout);
+    let client = TestClient::setup(endpoints[0].clone(), timeout).with_max_in_flight(job.max_in_flight);
+    let chain_id = client.chain_id().ok_or("failed to get chain id")?.as_u64();
+    // a soak test keeps no per-tx records, only the aggregated ones
+    let latency = match job.duration {
+        Some(_) => LatencyTracker::default().without_inclusion(),
+        None => LatencyTracker::default(),
+    };
+    let latency = Arc::new(latency);
+    let mut senders = setup_senders(&client, endpoints, timeout, None, chain_id, job.fee, &latency);
+    if job.duration.is_some() {
+        senders = senders.into_iter().map(Sender::without_records).collect();
+    }
+    let senders = Arc::new(senders);
+    let target_amount = U256::exp10(16); // 0.01 eth
+    let targets = target_count(job.count, job.duration.is_some());
+    let sources = prepare_sources(&client, &keys, targets, target_amount, job.check_balance);
+    if sources.is_empty() {
+        return Err("no source key has enough balance".to_string());
+    }
+
+    let height = client.block_number().ok_or("failed to get block number")?;
+    let ready = Message::Ready {
+        keys: sources.len(),
+        height: height.as_u64(),
+    };
+    rt.block_on(peer.send(&ready)).map_err(|e| e.to_string())?;
+    let start_height = match rt.block_on(peer.recv()).map_err(|e| e.to_string())? {
+        Message::Start { height } => U64::from(height),
+        other => return Err(format!("expect a start, got {:?}", other)),
+    };
+
+    let client_rt = client.runtime();
+    let watcher = {
+        let latency = latency.clone();
+        let client = senders[0].client.clone();
+        let timeout = Duration::from_secs(job.block_time * 3 + 1);
+        client_rt.spawn(async move { latency.watch_blocks(&client, start_height, timeout).await })
+    };
+    info!("starting rounds after height {}", start_height);
+    let stats = client_rt.block_on(round_loop(
+        senders.clone(),
+        Arc::new(sources),
+        job.duration.map_or(RunLimit::Count(job.count), |secs| {
+            RunLimit::Duration(Duration::from_secs(secs))
+        }),
+        job.pipeline,
+        job.delay,
+        start_height,
+    ));
+    let end_height = client.block_number().unwrap_or(start_height);
+    latency.finish();
+    info!("waiting for {} txs to be included...", latency.pending());
+    client_rt.block_on(watcher).unwrap();
+    info!(
+        "Worker result summary: total,{}/{},seconds,{:.3},height,{},{}",
+        stats.succeed, stats.total, stats.elapsed_secs, start_height, end_height
+    );
+
+    Ok(WorkerReport {
+        total: stats.total,
+        succeed: stats.succeed,
+        elapsed_secs: stats.elapsed_secs,
+        end_height: end_height.as_u64(),
+        latency: latency
+            .histograms()
+            .iter()
+            .filter_map(|(name, h)| encode(h).map(|encoded| (name.to_string(), encoded)))
+            .collect(),
+        errors: error_counts(&senders),
+        error_samples: error_samples(&senders),
+        metrics: transfer_metrics(&senders, &latency),
+    })
+}
+
+fn encode(h: &Histogram<u64>) -> Option<String> {
+    let mut buf = vec![];
+    V2Serializer::new().serialize(h, &mut buf).ok()?;
+    Some(hex::encode(buf))
+}
+
+fn decode(encoded: &str) -> Option<Histogram<u64>> {
+    let buf = hex::decode(encoded).ok()?;
+    Deserializer::new().deserialize(&mut buf.as_slice()).ok()
+}
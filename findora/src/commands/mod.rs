@@ -1,23 +1,34 @@
+mod scenario;
+
+use scenario::Scenario;
+
 use crate::{
+    bench::Method,
     db::{Db, Proto},
+    guard::Guard,
+    load::{Mix, Profile},
     profiler,
 };
 use chrono::NaiveDateTime;
-use clap::{Parser, Subcommand};
-use feth::{error::Result, BLOCK_TIME};
+use clap::{Args, CommandFactory, ErrorKind, FromArgMatches, Parser, Subcommand};
+use feth::{error::Result, report::RunResult, TxFee, BLOCK_TIME};
 use serde::{Deserialize, Serialize};
 use std::{
+    ffi::OsString,
     fmt::{Display, Formatter},
     io::BufRead,
+    net::SocketAddr,
     path::{Path, PathBuf},
     rc::Rc,
+    time::Duration,
 };
-use web3::types::{Address, H256};
+use web3::types::{Address, H256, U256};
 
 #[derive(Debug)]
 pub enum TestMode {
     Basic,
     Contract,
+    Mix(Mix),
 }
 
 impl std::str::FromStr for TestMode {
@@ -27,11 +38,93 @@ impl std::str::FromStr for TestMode {
         match s.to_lowercase().as_str() {
             "basic" => Ok(Self::Basic),
             "contract" => Ok(Self::Contract),
-            _ => Err("Invalid mode: basic and contract are supported".to_owned()),
+            mode if mode.starts_with("mix:") => mode[4..].parse::<Mix>().map(Self::Mix),
+            _ => Err("Invalid mode: basic, contract and mix:<kind=weight,..> are supported".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TxType {
+    Legacy,
+    AccessList,
+    DynamicFee,
+}
+
+impl std::str::FromStr for TxType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(Self::Legacy),
+            "access-list" => Ok(Self::AccessList),
+            "dynamic-fee" => Ok(Self::DynamicFee),
+            _ => Err("Invalid tx type: legacy, access-list and dynamic-fee are supported".to_owned()),
         }
     }
 }
 
+/// Type and fees of test transactions
+#[derive(Args, Debug, Clone)]
+pub struct FeeOpts {
+    /// Transaction type: legacy, access-list(EIP-2930) or dynamic-fee(EIP-1559)
+    #[clap(long, default_value = "legacy")]
+    pub tx_type: TxType,
+
+    /// Max fee per gas of dynamic-fee transactions in wei, the gas price of the network by default
+    #[clap(long)]
+    pub max_fee: Option<u64>,
+
+    /// Max priority fee per gas of dynamic-fee transactions in wei, the max fee by default
+    #[clap(long)]
+    pub priority_fee: Option<u64>,
+}
+
+impl FeeOpts {
+    pub fn tx_fee(&self, gas_price: U256) -> TxFee {
+        match self.tx_type {
+            TxType::Legacy => TxFee::Legacy { gas_price },
+            TxType::AccessList => TxFee::AccessList { gas_price },
+            TxType::DynamicFee => {
+                let max_fee = self.max_fee.map(U256::from).unwrap_or(gas_price);
+                let priority_fee = self.priority_fee.map(U256::from).unwrap_or(max_fee);
+                TxFee::DynamicFee { max_fee, priority_fee }
+            }
+        }
+    }
+}
+
+/// Thresholds to abort a test, the partial results are still reported
+#[derive(Args, Debug)]
+pub struct GuardOpts {
+    /// Stop when the percent of failed sends exceeds it, checked after 100 sends
+    #[clap(long, value_name = "PERCENT")]
+    pub max_error_rate: Option<f64>,
+
+    /// Stop after the number of consecutive SyncTx errors
+    #[clap(long)]
+    pub max_sync_errors: Option<u64>,
+
+    /// Stop when the balance of a source key drops below it, in eth, e.g. 0.1
+    #[clap(long, value_name = "ETH")]
+    pub min_balance: Option<f64>,
+
+    /// Stop when there's no new block in the seconds
+    #[clap(long, value_name = "SECS")]
+    pub max_stall: Option<u64>,
+}
+
+impl GuardOpts {
+    pub fn guard(&self) -> Guard {
+        Guard::new(
+            self.max_error_rate,
+            self.max_sync_errors,
+            self.min_balance.map(|eth| U256::from((eth * 1e18) as u128)),
+            self.max_stall.map(Duration::from_secs),
+        )
+    }
+}
+
 #[derive(Debug)]
 pub enum Network {
     Local,
@@ -98,6 +191,8 @@ impl std::str::FromStr for Network {
                     Err("QA env num is a 32-bit integer".to_owned())
                 };
             }
+            // one or more comma separated endpoints
+            network if network.starts_with("http") => Ok(Self::Node(network.to_owned())),
             network if network.starts_with("node") => {
                 let segs: Vec<&str> = network.splitn(2, ',').collect();
                 if let Some(node) = segs.get(1) {
@@ -260,7 +355,42 @@ where
 
 impl Cli {
     pub(crate) fn parse_args() -> Self {
-        Cli::parse()
+        Cli::try_parse_args_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// parse `args`, with the scenario of `feth test` filling the flags not given
+    pub(crate) fn try_parse_args_from<I, T>(args: I) -> std::result::Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Cli::command().try_get_matches_from(args)?;
+        let mut cli = Cli::from_arg_matches(&matches)?;
+        if let (
+            Some(Commands::Test {
+                scenario: Some(path), ..
+            }),
+            Some(test),
+        ) = (&cli.command, matches.subcommand_matches("test"))
+        {
+            let path = path.clone();
+            let merged = Scenario::load(&path).and_then(|scenario| {
+                let command = cli.command.as_mut().unwrap();
+                scenario
+                    .merge(command, test)
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+            });
+            if let Err(e) = merged {
+                return Err(Cli::command().error(ErrorKind::InvalidValue, e));
+            }
+        }
+        if let Some(Commands::Test { network, mode, .. }) = &cli.command {
+            if network.is_none() || mode.is_none() {
+                let e = "--network and --mode are required, on the command line or in the scenario";
+                return Err(Cli::command().error(ErrorKind::MissingRequiredArgument, e));
+            }
+        }
+        Ok(cli)
     }
 
     pub(crate) fn etl_cmd<P>(abcid: &Option<P>, tendermint: &Option<P>, redis: &str, load: bool) -> Result<()>
@@ -305,6 +435,25 @@ impl Cli {
         Ok(())
     }
 
+    /// print the changes of `current` against `base`, returns if any metric regressed
+    pub(crate) fn compare<P: AsRef<Path>>(base: P, current: P, threshold: f64) -> Result<bool> {
+        let base = RunResult::load(base)?;
+        let current = RunResult::load(current)?;
+        let changes = current.compare(&base, threshold);
+        println!("metric,base,current,change(%),regressed");
+        changes.iter().for_each(|c| {
+            println!(
+                "{},{:.3},{:.3},{:.2},{}",
+                c.name,
+                c.base,
+                c.current,
+                c.percent(),
+                c.regressed
+            );
+        });
+        Ok(changes.iter().any(|c| c.regressed))
+    }
+
     pub(crate) fn profiler(network: &str, enabled: bool) -> Result<()> {
         let url = format!("{}/configuration", network);
         profiler::set_profiler(url.as_str(), enabled)
@@ -342,6 +491,53 @@ pub enum Commands {
         /// re-deposit account with insufficient balance
         #[clap(long)]
         redeposit: bool,
+
+        /// derive new keys at m/44'/60'/0'/0/i from the mnemonic in the file, a new one is saved if missing,
+        /// only the file and the range are saved to the key file, the range follows an existing set of it
+        #[clap(long, parse(from_os_str), value_name = "FILE", conflicts_with = "load")]
+        seed: Option<PathBuf>,
+
+        /// show a live dashboard instead of the log lines, which go to feth.log
+        #[clap(long)]
+        tui: bool,
+
+        /// Serve live metrics in the Prometheus text format on the address, at /metrics
+        #[clap(long, value_name = "ADDR")]
+        metrics_listen: Option<SocketAddr>,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
+    },
+    /// drain source keys back to the root account or a given address
+    Sweep {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// block time of the network
+        #[clap(long, default_value_t = BLOCK_TIME)]
+        block_time: u64,
+
+        /// the source account file
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// where the funds go, the root account by default
+        #[clap(long)]
+        to: Option<Address>,
+
+        /// The max thread pool size for sweeping
+        #[clap(long, default_value_t = 200)]
+        max_threads: u64,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
     },
     /// check ethereum account information
     Info {
@@ -424,23 +620,29 @@ pub enum Commands {
 
     /// Test
     Test {
-        /// Ethereum web3-compatible network
-        #[clap(long)]
-        network: Network,
+        /// Scenario file in TOML or YAML, fields are named after the flags, which override them
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        scenario: Option<PathBuf>,
 
-        /// Test mode: basic transfer transaction, contract call transaction
-        #[clap(long)]
-        mode: TestMode,
+        /// Ethereum web3-compatible network, source keys are divided to comma separated endpoints
+        #[clap(long, required_unless_present = "scenario")]
+        network: Option<Network>,
+
+        /// Test mode: basic transfer transaction, contract call transaction(ERC20 transfer),
+        /// or a weighted mix of transfer, token, deploy, call(eth_call) and balance(eth_getBalance),
+        /// e.g. mix:transfer=60,token=20,deploy=5,call=10,balance=5
+        #[clap(long, required_unless_present = "scenario")]
+        mode: Option<TestMode>,
 
         /// Delay time for next batch of transactions
         #[clap(long, default_value_t = 15)]
         delay: u64,
 
-        /// The max thread pool size
+        /// The max thread pool size for preparing test data
         #[clap(long, default_value_t = 200)]
         max_threads: u64,
 
-        /// The count of transactions sent by a source key
+        /// The count of transactions sent by a source key, or its targets reused by a soak test or a profile
         #[clap(long, default_value_t = 0)]
         count: u64,
 
@@ -456,12 +658,262 @@ pub enum Commands {
         #[clap(long, default_value_t = 60)]
         timeout: u64,
 
+        /// WebSocket endpoint to follow new blocks with a `newHeads` subscription, instead of polling
+        #[clap(long, value_name = "URL")]
+        ws: Option<String>,
+
         /// if need to retry to sending transactions
-        #[clap(long)]
+        #[clap(long, overrides_with = "no-need-retry")]
         need_retry: bool,
 
+        /// Turn off --need-retry of the scenario
+        #[clap(long, overrides_with = "need-retry")]
+        no_need_retry: bool,
+
+        /// If need to check balance of source keys
+        #[clap(long, overrides_with = "no-check-balance")]
+        check_balance: bool,
+
+        /// Turn off --check-balance of the scenario
+        #[clap(long, overrides_with = "check-balance")]
+        no_check_balance: bool,
+
+        /// Send transactions at a constant rate(tps) instead of one round per block
+        #[clap(long)]
+        rate: Option<u64>,
+
+        /// Load profile, comma separated stages of kind:seconds:rate[-rate],
+        /// e.g. ramp:60:100-1000,step:120:1000,spike:30:1000-3000,sine:300:500-1500
+        #[clap(long, conflicts_with = "rate")]
+        profile: Option<Profile>,
+
+        #[clap(flatten)]
+        fee: FeeOpts,
+
+        // boxed to keep the variants of `Commands` close in size
+        #[clap(flatten)]
+        guard: Box<GuardOpts>,
+
+        /// The number of transactions a source key sends in one round, with local nonces
+        #[clap(long, default_value_t = 1)]
+        pipeline: u64,
+
+        /// The max number of in-flight requests per endpoint, fewer while the node pushes back
+        #[clap(long, default_value_t = 10000)]
+        max_in_flight: usize,
+
+        /// Soak test, keep sending for the seconds, targets of source keys are reused
+        #[clap(long)]
+        duration: Option<u64>,
+
+        /// Serve live metrics in the Prometheus text format on the address, at /metrics
+        #[clap(long, value_name = "ADDR")]
+        metrics_listen: Option<SocketAddr>,
+
+        /// The interval of aggregated summaries in a soak test, seconds
+        #[clap(long, default_value_t = 60)]
+        report_interval: u64,
+
+        /// Show a live dashboard instead of the log lines, which go to feth.log
+        #[clap(long, overrides_with = "no-tui")]
+        tui: bool,
+
+        /// Turn off --tui of the scenario
+        #[clap(long, overrides_with = "tui")]
+        no_tui: bool,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
+    },
+    /// Build and sign test transactions ahead of time, save them to a corpus file
+    Presign {
+        /// Ethereum web3-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// the source account file
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// The count of transactions signed by a source key
+        #[clap(long, default_value_t = 1)]
+        count: u64,
+
+        /// ERC20 token to transfer, value transfers if not set
+        #[clap(long)]
+        token: Option<Address>,
+
+        #[clap(flatten)]
+        fee: FeeOpts,
+
+        /// The max thread pool size for signing
+        #[clap(long, default_value_t = 200)]
+        max_threads: u64,
+
+        /// the corpus file to save signed transactions
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "corpus.json")]
+        corpus: PathBuf,
+    },
+    /// Send pre-signed transactions of a corpus file
+    Replay {
+        /// Ethereum web3-compatible network, source keys are divided to comma separated endpoints
+        #[clap(long)]
+        network: Network,
+
+        /// the corpus file of signed transactions
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "corpus.json")]
+        corpus: PathBuf,
+
+        /// block time of the network
+        #[clap(long, default_value_t = BLOCK_TIME)]
+        block_time: u64,
+
+        /// http request timeout, seconds
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// WebSocket endpoint to follow new blocks with a `newHeads` subscription, instead of polling
+        #[clap(long, value_name = "URL")]
+        ws: Option<String>,
+
+        /// The max number of in-flight requests per endpoint, fewer while the node pushes back
+        #[clap(long, default_value_t = 10000)]
+        max_in_flight: usize,
+
+        /// Serve live metrics in the Prometheus text format on the address, at /metrics
+        #[clap(long, value_name = "ADDR")]
+        metrics_listen: Option<SocketAddr>,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
+    },
+    /// Split the source keys among workers and run a test on all of them in sync.
+    ///
+    /// Jobs go to workers in plain text without authentication. A key set is sent as its range only,
+    /// a file of key pairs with the private keys, so keep workers on a trusted network.
+    Coordinate {
+        /// Ethereum web3-compatible network, source keys of a worker are divided to comma separated endpoints
+        #[clap(long)]
+        network: Network,
+
+        /// Comma separated addresses of `feth worker`s
+        #[clap(long, value_name = "ADDR", use_value_delimiter = true, required = true)]
+        workers: Vec<SocketAddr>,
+
+        /// the source account file, split into contiguous shards of workers, a key set is derived by
+        /// workers from their own copy of the mnemonic
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// The count of transactions sent by a source key, or its targets reused by a soak test
+        #[clap(long, default_value_t = 0)]
+        count: u64,
+
+        /// The number of transactions a source key sends in one round, with local nonces
+        #[clap(long, default_value_t = 1)]
+        pipeline: u64,
+
+        /// Delay time for next batch of transactions
+        #[clap(long, default_value_t = 15)]
+        delay: u64,
+
+        /// Soak test, keep sending for the seconds, targets of source keys are reused
+        #[clap(long)]
+        duration: Option<u64>,
+
+        /// The max number of in-flight requests per endpoint of a worker
+        #[clap(long, default_value_t = 10000)]
+        max_in_flight: usize,
+
         /// If need to check balance of source keys
         #[clap(long)]
         check_balance: bool,
+
+        #[clap(flatten)]
+        fee: FeeOpts,
+
+        /// block time of the network
+        #[clap(long, default_value_t = BLOCK_TIME)]
+        block_time: u64,
+
+        /// http request timeout, seconds
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
+    },
+    /// Wait for shards of tests from a coordinator and run them.
+    ///
+    /// Anyone reaching the address can hand out a job, which may carry private keys in plain text,
+    /// listen on a trusted network only.
+    Worker {
+        /// The address to listen on, the loopback by default, e.g. 10.0.0.2:7878 to take jobs of other hosts
+        #[clap(long, value_name = "ADDR", default_value = "127.0.0.1:7878")]
+        listen: SocketAddr,
+
+        /// the mnemonic file of key sets, in place of the path given by the coordinator
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        seed: Option<PathBuf>,
+    },
+    /// Benchmark read-only RPC methods
+    BenchRpc {
+        /// Ethereum web3-compatible network, workers are divided to comma separated endpoints
+        #[clap(long)]
+        network: Network,
+
+        /// Weighted mix of balance, nonce, block, receipt, code and chain_id, e.g. balance=3,block=1
+        #[clap(long, default_value = "balance=1,nonce=1,block=1,receipt=1,code=1,chain_id=1")]
+        methods: Mix<Method>,
+
+        /// The number of concurrent workers
+        #[clap(long, default_value_t = 100)]
+        concurrency: usize,
+
+        /// Keep sending for the seconds
+        #[clap(long, default_value_t = 30)]
+        duration: u64,
+
+        /// The total number of requests, instead of a duration
+        #[clap(long)]
+        count: Option<u64>,
+
+        /// http request timeout, seconds
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// directory to save the run results
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = "runs")]
+        output: PathBuf,
+    },
+    /// Analyze saved test runs
+    Report {
+        #[clap(subcommand)]
+        command: ReportCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReportCommands {
+    /// Compare a test run with a base run, exit with 1 on regressions
+    Compare {
+        /// the base run directory or result file
+        #[clap(parse(from_os_str))]
+        base: PathBuf,
+
+        /// the run directory or result file to check
+        #[clap(parse(from_os_str))]
+        current: PathBuf,
+
+        /// the max allowed change for the worse, percent, error rates are compared in percent of sends
+        #[clap(long, default_value_t = 5.0)]
+        threshold: f64,
     },
 }
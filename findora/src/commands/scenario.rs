@@ -0,0 +1,283 @@
+use super::Commands;
+use clap::{ArgMatches, ValueSource};
+use serde::Deserialize;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A test run described in a TOML or YAML file, fields are named after the flags of `feth test`.
+///
+/// The fields fill the flags that aren't given on the command line, a switch set in the file is
+/// turned off by its `--no-` flag. Paths are relative to the working directory like the flags,
+/// not to the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Scenario {
+    pub network: Option<String>,
+    /// the source key file
+    pub source: Option<PathBuf>,
+    /// workload kind, in the syntax of `--mode`
+    pub mode: Option<String>,
+    pub count: Option<u64>,
+    pub pipeline: Option<u64>,
+    pub delay: Option<u64>,
+    pub rate: Option<u64>,
+    /// stages of the load profile, in the syntax of `--profile` or one stage per item
+    pub profile: Option<Stages>,
+    pub duration: Option<u64>,
+    pub max_threads: Option<u64>,
+    pub max_in_flight: Option<usize>,
+    pub block_time: Option<u64>,
+    pub timeout: Option<u64>,
+    pub ws: Option<String>,
+    pub need_retry: Option<bool>,
+    pub check_balance: Option<bool>,
+    pub metrics_listen: Option<String>,
+    pub report_interval: Option<u64>,
+    pub tui: Option<bool>,
+    pub output: Option<PathBuf>,
+    pub fee: FeeScenario,
+    pub guard: GuardScenario,
+}
+
+/// Fields of `FeeOpts`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct FeeScenario {
+    pub tx_type: Option<String>,
+    pub max_fee: Option<u64>,
+    pub priority_fee: Option<u64>,
+}
+
+/// Fields of `GuardOpts`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct GuardScenario {
+    pub max_error_rate: Option<f64>,
+    pub max_sync_errors: Option<u64>,
+    /// eth
+    pub min_balance: Option<f64>,
+    pub max_stall: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Stages {
+    Joined(String),
+    List(Vec<String>),
+}
+
+impl Display for Stages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stages::Joined(stages) => write!(f, "{}", stages),
+            Stages::List(stages) => write!(f, "{}", stages.join(",")),
+        }
+    }
+}
+
+impl Scenario {
+    /// load a `.toml`, `.yaml` or `.yml` file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e)),
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&data).map_err(|e| format!("{}: {}", path.display(), e)),
+            _ => Err(format!(
+                "{}: unknown scenario format, toml or yaml expected",
+                path.display()
+            )),
+        }
+    }
+
+    /// Fill the flags of `feth test` that aren't given on the command line, `matches` are of
+    /// the subcommand.
+    pub fn merge(self, test: &mut Commands, matches: &ArgMatches) -> Result<(), String> {
+        let m = Merge(matches);
+        if let Commands::Test {
+            network,
+            mode,
+            delay,
+            max_threads,
+            count,
+            source,
+            block_time,
+            timeout,
+            ws,
+            need_retry,
+            check_balance,
+            rate,
+            profile,
+            fee,
+            guard,
+            pipeline,
+            max_in_flight,
+            duration,
+            metrics_listen,
+            report_interval,
+            tui,
+            output,
+            ..
+        } = test
+        {
+            m.set("network", network, parse(self.network)?.map(Some));
+            m.set("mode", mode, parse(self.mode)?.map(Some));
+            m.set("source", source, self.source);
+            m.set("count", count, self.count);
+            m.set("pipeline", pipeline, self.pipeline);
+            m.set("delay", delay, self.delay);
+            // a rate or a profile on the command line replaces both of the file
+            if !m.given("rate") && !m.given("profile") {
+                *rate = self.rate.or(*rate);
+                *profile = parse(self.profile.map(|stages| stages.to_string()))?.or(profile.take());
+                if rate.is_some() && profile.is_some() {
+                    return Err("rate and profile can't be used together".to_owned());
+                }
+            }
+            m.set("duration", duration, self.duration.map(Some));
+            m.set("max-threads", max_threads, self.max_threads);
+            m.set("max-in-flight", max_in_flight, self.max_in_flight);
+            m.set("block-time", block_time, self.block_time);
+            m.set("timeout", timeout, self.timeout);
+            m.set("ws", ws, self.ws.map(Some));
+            m.switch("need-retry", need_retry, self.need_retry);
+            m.switch("check-balance", check_balance, self.check_balance);
+            m.set("metrics-listen", metrics_listen, parse(self.metrics_listen)?.map(Some));
+            m.set("report-interval", report_interval, self.report_interval);
+            m.switch("tui", tui, self.tui);
+            m.set("output", output, self.output);
+            m.set("tx-type", &mut fee.tx_type, parse(self.fee.tx_type)?);
+            m.set("max-fee", &mut fee.max_fee, self.fee.max_fee.map(Some));
+            m.set("priority-fee", &mut fee.priority_fee, self.fee.priority_fee.map(Some));
+            m.set(
+                "max-error-rate",
+                &mut guard.max_error_rate,
+                self.guard.max_error_rate.map(Some),
+            );
+            m.set(
+                "max-sync-errors",
+                &mut guard.max_sync_errors,
+                self.guard.max_sync_errors.map(Some),
+            );
+            m.set("min-balance", &mut guard.min_balance, self.guard.min_balance.map(Some));
+            m.set("max-stall", &mut guard.max_stall, self.guard.max_stall.map(Some));
+        }
+        Ok(())
+    }
+}
+
+/// parse a field in the syntax of its flag
+fn parse<T>(value: Option<String>) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .map(|value| value.parse().map_err(|e| format!("{}: {}", value, e)))
+        .transpose()
+}
+
+struct Merge<'a>(&'a ArgMatches);
+
+impl Merge<'_> {
+    /// the flag of `id` is on the command line
+    fn given(&self, id: &str) -> bool {
+        self.0.value_source(id) == Some(ValueSource::CommandLine)
+    }
+
+    /// set the field to the value of the file, unless the flag is given
+    fn set<T>(&self, id: &str, field: &mut T, value: Option<T>) {
+        if let Some(value) = value.filter(|_| !self.given(id)) {
+            *field = value;
+        }
+    }
+
+    /// like `set`, a switch is also kept by its `--no-` flag
+    fn switch(&self, id: &str, field: &mut bool, value: Option<bool>) {
+        if !self.given(&format!("no-{}", id)) {
+            self.set(id, field, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{Cli, Network, TestMode};
+    use clap::ErrorKind;
+
+    /// `feth test` with the scenario `name`.toml of `fields` and `args`
+    fn parse(name: &str, fields: &str, args: &[&str]) -> Result<Commands, clap::Error> {
+        let path = std::env::temp_dir().join(format!("feth-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, fields).unwrap();
+        let path = path.display().to_string();
+        let cli = Cli::try_parse_args_from(["feth", "test", "--scenario", &path].iter().chain(args));
+        std::fs::remove_file(path).unwrap();
+        cli.map(|cli| cli.command.unwrap())
+    }
+
+    #[test]
+    fn flags_override_fields() {
+        let fields = "network = \"local\"\nmode = \"basic\"\ncount = 100\ndelay = 0\n[guard]\nmax_stall = 60";
+        match parse("override", fields, &["--count", "5", "--mode", "contract"]).unwrap() {
+            Commands::Test {
+                network,
+                mode,
+                count,
+                delay,
+                guard,
+                ..
+            } => {
+                assert!(matches!(network, Some(Network::Local)));
+                assert!(matches!(mode, Some(TestMode::Contract)));
+                assert_eq!((count, delay), (5, 0));
+                assert_eq!(guard.max_stall, Some(60));
+            }
+            other => panic!("expect test, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rate_or_profile_replaces_both() {
+        let fields = "network = \"local\"\nmode = \"basic\"\nprofile = \"step:60:100\"";
+        match parse("rate", fields, &["--rate", "10"]).unwrap() {
+            Commands::Test { rate, profile, .. } => assert_eq!((rate, profile), (Some(10), None)),
+            other => panic!("expect test, got {:?}", other),
+        }
+        let fields = "network = \"local\"\nmode = \"basic\"\nrate = 100";
+        match parse("profile", fields, &["--profile", "step:60:100"]).unwrap() {
+            Commands::Test { rate, profile, .. } => {
+                assert_eq!(rate, None);
+                assert_eq!(profile, Some("step:60:100".parse().unwrap()));
+            }
+            other => panic!("expect test, got {:?}", other),
+        }
+        let fields = "network = \"local\"\nmode = \"basic\"\nrate = 100\nprofile = \"step:60:100\"";
+        assert!(parse("both", fields, &[]).is_err());
+    }
+
+    #[test]
+    fn no_flags_turn_off_switches() {
+        let fields = "network = \"local\"\nmode = \"basic\"\ntui = true\ncheck_balance = true";
+        match parse("switch", fields, &["--no-tui"]).unwrap() {
+            Commands::Test { tui, check_balance, .. } => assert_eq!((tui, check_balance), (false, true)),
+            other => panic!("expect test, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_fields_rejected() {
+        let fields = "network = \"local\"\nmode = \"basic\"\ncout = 5";
+        let e = parse("unknown", fields, &[]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidValue);
+        assert!(e.to_string().contains("unknown field `cout`"), "{}", e);
+    }
+
+    #[test]
+    fn network_and_mode_required() {
+        let e = parse("required", "count = 5", &["--network", "local"]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::MissingRequiredArgument);
+    }
+}
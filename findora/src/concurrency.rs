@@ -0,0 +1,213 @@
+use crate::error::Error;
+use log::{debug, warn};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// back-pressure errors within this interval are taken as one congestion event
+const DECREASE_INTERVAL: Duration = Duration::from_secs(1);
+/// the most slots, a larger `max` is taken as unlimited
+const MAX_SLOTS: usize = u32::MAX as usize;
+
+/// An adaptive limit of in-flight sends, with additive increase and multiplicative decrease.
+///
+/// Every send holds a `Permit` while it's in flight, and reports its result. The limit grows by one
+/// after as many accepted sends as the limit, up to `max`, and halves on back-pressure errors, down
+/// to one send at a time. Waiting sends are woken one per free slot, in the order they came.
+#[derive(Debug)]
+pub struct Concurrency {
+    max: usize,
+    state: Mutex<State>,
+    /// one permit per free slot under the limit
+    slots: Semaphore,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    in_flight: usize,
+    /// accepted sends since the last change of the limit
+    accepted: usize,
+    last_decrease: Option<Instant>,
+    /// slots over the limit after a decrease, they're taken away once their sends finish
+    excess: usize,
+}
+
+/// A slot of in-flight send, released on drop
+#[derive(Debug)]
+pub struct Permit<'a> {
+    concurrency: &'a Concurrency,
+    slot: Option<SemaphorePermit<'a>>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.concurrency.state.lock().unwrap();
+        state.in_flight -= 1;
+        if state.excess > 0 {
+            state.excess -= 1;
+            if let Some(slot) = self.slot.take() {
+                slot.forget();
+            }
+        }
+    }
+}
+
+impl Concurrency {
+    /// start with `max` sends in flight
+    pub fn new(max: usize) -> Self {
+        let max = max.clamp(1, MAX_SLOTS);
+        Self {
+            max,
+            state: Mutex::new(State {
+                limit: max,
+                in_flight: 0,
+                accepted: 0,
+                last_decrease: None,
+                excess: 0,
+            }),
+            slots: Semaphore::new(max),
+        }
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// sends allowed in flight now
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// wait until a send is allowed
+    pub async fn acquire(&self) -> Permit<'_> {
+        // never closed
+        let slot = self.slots.acquire().await.unwrap();
+        self.state.lock().unwrap().in_flight += 1;
+        Permit {
+            concurrency: self,
+            slot: Some(slot),
+        }
+    }
+
+    /// a send was accepted by the node
+    pub fn succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.accepted += 1;
+        if state.accepted >= state.limit && state.limit < self.max {
+            state.limit += 1;
+            state.accepted = 0;
+            if state.excess > 0 {
+                state.excess -= 1;
+            } else {
+                self.slots.add_permits(1);
+            }
+            debug!("concurrency limit increased to {}", state.limit);
+        }
+    }
+
+    /// a send failed, the limit is decreased if the node pushed back
+    pub fn failed(&self, e: &Error) {
+        if !e.is_back_pressure() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.last_decrease.is_some_and(|t| t.elapsed() < DECREASE_INTERVAL) {
+            return;
+        }
+        let limit = (state.limit.min(state.in_flight.max(1)) / 2).max(1);
+        // free slots are taken away now, the busy ones once their sends finish
+        let mut removed = state.limit - limit;
+        let free = removed.min(self.slots.available_permits());
+        if let Ok(slots) = self.slots.try_acquire_many(free as u32) {
+            slots.forget();
+            removed -= free;
+        }
+        state.excess += removed;
+        state.limit = limit;
+        state.accepted = 0;
+        state.last_decrease = Some(Instant::now());
+        warn!("{}, concurrency limit decreased to {}", e.category(), state.limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::runtime::{Builder, Runtime};
+
+    fn runtime() -> Runtime {
+        Builder::new_current_thread().build().unwrap()
+    }
+
+    fn acquire<'a>(rt: &Runtime, concurrency: &'a Concurrency, count: usize) -> Vec<Permit<'a>> {
+        (0..count).map(|_| rt.block_on(concurrency.acquire())).collect()
+    }
+
+    #[test]
+    fn halve_on_back_pressure() {
+        let rt = runtime();
+        let concurrency = Concurrency::new(8);
+        let _permits = acquire(&rt, &concurrency, 8);
+        concurrency.failed(&Error::Unknown("reverted".to_owned()));
+        assert_eq!(concurrency.limit(), 8);
+        concurrency.failed(&Error::Http(503));
+        assert_eq!(concurrency.limit(), 4);
+        // within the interval of one congestion event
+        concurrency.failed(&Error::Http(503));
+        assert_eq!(concurrency.limit(), 4);
+    }
+
+    #[test]
+    fn grow_by_one_up_to_max() {
+        let concurrency = Concurrency::new(3);
+        concurrency.failed(&Error::Http(429));
+        assert_eq!(concurrency.limit(), 1);
+        concurrency.succeeded();
+        assert_eq!(concurrency.limit(), 2);
+        concurrency.succeeded();
+        assert_eq!(concurrency.limit(), 2);
+        concurrency.succeeded();
+        assert_eq!(concurrency.limit(), 3);
+        (0..10).for_each(|_| concurrency.succeeded());
+        assert_eq!(concurrency.limit(), 3);
+        assert_eq!(concurrency.slots.available_permits(), 3);
+    }
+
+    #[test]
+    fn no_slot_leaks_after_decrease() {
+        let rt = runtime();
+        let concurrency = Concurrency::new(4);
+        let permits = acquire(&rt, &concurrency, 4);
+        concurrency.failed(&Error::Http(503));
+        assert_eq!(concurrency.limit(), 2);
+        // an increase takes back an excess slot instead of adding one
+        concurrency.succeeded();
+        concurrency.succeeded();
+        assert_eq!(concurrency.limit(), 3);
+        assert_eq!(concurrency.slots.available_permits(), 0);
+        drop(permits);
+        assert_eq!(concurrency.in_flight(), 0);
+        assert_eq!(concurrency.slots.available_permits(), 3);
+        let _permits = acquire(&rt, &concurrency, 3);
+        assert_eq!(concurrency.slots.available_permits(), 0);
+    }
+
+    #[test]
+    fn free_slots_taken_away_on_decrease() {
+        let rt = runtime();
+        let concurrency = Concurrency::new(8);
+        let permits = acquire(&rt, &concurrency, 4);
+        concurrency.failed(&Error::Http(503));
+        assert_eq!(concurrency.limit(), 2);
+        assert_eq!(concurrency.slots.available_permits(), 0);
+        drop(permits);
+        assert_eq!(concurrency.slots.available_permits(), 2);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::error::{Error, Result};
+use web3::{
+    ethabi::{Contract, Token},
+    types::{Address, Bytes, U256},
+};
+
+/// ABI of the bundled ERC20-style test token (HumanStandardToken)
+const ERC20_ABI: &[u8] = include_bytes!("../contracts/erc20.abi");
+/// Creation bytecode of the bundled test token, hex encoded
+const ERC20_BIN: &str = include_str!("../contracts/erc20.bin");
+
+/// gas limit for deploying the test token
+pub const DEPLOY_GAS: u64 = 3_000_000;
+/// gas limit for a token transfer
+pub const TRANSFER_GAS: u64 = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct Erc20 {
+    pub address: Option<Address>,
+    abi: Contract,
+}
+
+impl Default for Erc20 {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Erc20 {
+    pub fn new(address: Option<Address>) -> Self {
+        Self {
+            address,
+            abi: Contract::load(ERC20_ABI).unwrap(),
+        }
+    }
+
+    /// creation data for a token with `supply` owned by the deployer
+    pub fn deploy_data(&self, supply: U256, name: &str, decimals: u8, symbol: &str) -> Result<Bytes> {
+        let code = hex::decode(ERC20_BIN.trim()).map_err(|e| Error::Unknown(e.to_string()))?;
+        let constructor = self
+            .abi
+            .constructor()
+            .ok_or_else(|| Error::Unknown("constructor not found".to_string()))?;
+        constructor
+            .encode_input(
+                code,
+                &[
+                    Token::Uint(supply),
+                    Token::String(name.to_owned()),
+                    Token::Uint(U256::from(decimals)),
+                    Token::String(symbol.to_owned()),
+                ],
+            )
+            .map(Bytes)
+            .map_err(|e| Error::Unknown(e.to_string()))
+    }
+
+    /// call data of `transfer(to, amount)`
+    pub fn transfer_data(&self, to: Address, amount: U256) -> Result<Bytes> {
+        self.encode("transfer", &[Token::Address(to), Token::Uint(amount)])
+    }
+
+    /// call data of `balanceOf(owner)`
+    pub fn balance_of_data(&self, owner: Address) -> Result<Bytes> {
+        self.encode("balanceOf", &[Token::Address(owner)])
+    }
+
+    pub fn decode_balance(&self, data: &[u8]) -> Option<U256> {
+        self.abi
+            .function("balanceOf")
+            .ok()?
+            .decode_output(data)
+            .ok()?
+            .into_iter()
+            .next()?
+            .into_uint()
+    }
+
+    fn encode(&self, func: &str, params: &[Token]) -> Result<Bytes> {
+        self.abi
+            .function(func)
+            .and_then(|f| f.encode_input(params))
+            .map(Bytes)
+            .map_err(|e| Error::Unknown(e.to_string()))
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{error::Result, TxFee};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use web3::types::{Address, Bytes, H256, U256};
+
+/// A signed transaction, `raw` is its RLP encoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTx {
+    pub from: Address,
+    pub to: Address,
+    pub amount: U256,
+    pub nonce: U256,
+    pub hash: H256,
+    pub raw: Bytes,
+}
+
+/// Pre-signed transactions of a workload, grouped by source account in nonce order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Corpus {
+    pub chain_id: u64,
+    pub fee: TxFee,
+    /// the token transferred, value transfers if none
+    pub token: Option<Address>,
+    pub sources: Vec<Vec<SignedTx>>,
+}
+
+impl Corpus {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(data.as_str())?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let data = serde_json::to_string(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// the number of transactions
+    pub fn len(&self) -> usize {
+        self.sources.iter().map(|txs| txs.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,398 @@
+use crate::load::{error_counts, key_progress, Sender};
+use crossterm::{
+    cursor::Show,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use feth::{latency::LatencyTracker, progress::Progress};
+use log::error;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{self, Stdout},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{Axis, BarChart, Block, Borders, Chart, Dataset, GraphType, Paragraph, Row, Sparkline, Table},
+    Frame, Terminal,
+};
+use web3::types::Address;
+
+/// where the logs go while a dashboard is shown
+pub(crate) const LOG_FILE: &str = "feth.log";
+/// interval of sampling and drawing
+const TICK: Duration = Duration::from_secs(1);
+/// seconds of rates in the chart
+const WINDOW: usize = 120;
+/// blocks kept for the block time
+const BLOCKS: usize = 60;
+
+/// Counters of a run, taken every tick
+#[derive(Debug, Default)]
+pub(crate) struct Snapshot {
+    pub sent: u64,
+    pub succeed: u64,
+    pub failed: u64,
+    pub height: u64,
+    /// failed sends by error category
+    pub errors: BTreeMap<String, u64>,
+    /// sent and succeeded txs of every source key
+    pub keys: BTreeMap<Address, (u64, u64)>,
+    /// txs to send by every key, unknown if zero
+    pub per_key: u64,
+}
+
+impl Snapshot {
+    /// counters of a load test, `per_key` txs by every source key
+    pub fn of_senders(senders: &[Sender], latency: &LatencyTracker, per_key: u64) -> Self {
+        let succeed = senders.iter().map(|s| s.succeed.load(Relaxed)).sum::<u64>();
+        let failed = senders.iter().map(|s| s.failed.load(Relaxed)).sum::<u64>();
+        Self {
+            sent: succeed + failed,
+            succeed,
+            failed,
+            height: latency.height(),
+            errors: error_counts(senders),
+            keys: key_progress(senders),
+            per_key,
+        }
+    }
+
+    /// counters of a `distribution`, every target is a key with one tx
+    pub fn of_progress(progress: &Progress, height: u64) -> Self {
+        Self {
+            sent: progress.attempts(),
+            succeed: progress.succeeded(),
+            failed: progress.failures(),
+            height,
+            errors: progress
+                .errors()
+                .into_iter()
+                .map(|(category, count)| (category.to_string(), count))
+                .collect(),
+            keys: progress.targets(),
+            per_key: 1,
+        }
+    }
+}
+
+/// A live view of a run in the terminal, in place of the log lines.
+///
+/// `q` closes the view and the run goes on, Ctrl-C closes it and calls `interrupt` to stop the run.
+pub(crate) struct Dashboard {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Dashboard {
+    /// draw the snapshots of `sample` until `stop`
+    pub fn start<F, I>(title: String, sample: F, interrupt: I) -> Self
+    where
+        F: FnMut() -> Snapshot + Send + 'static,
+        I: FnOnce() + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = stop.clone();
+            std::thread::spawn(move || match run(&title, sample, &stop) {
+                // the terminal is restored, another Ctrl-C kills the process
+                Ok(true) => interrupt(),
+                Ok(false) => {}
+                Err(e) => error!("dashboard failed: {}", e),
+            })
+        };
+        Self { stop, handle }
+    }
+
+    /// restore the terminal, with the last snapshot drawn
+    pub fn stop(self) {
+        self.stop.store(true, Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+/// what has been seen since the start
+struct History {
+    start: Instant,
+    last: Snapshot,
+    /// sent and succeeded txs per second, by seconds since the start
+    sent: VecDeque<(f64, f64)>,
+    succeed: VecDeque<(f64, f64)>,
+    /// new heights and when they were seen
+    blocks: VecDeque<(u64, Instant)>,
+}
+
+impl History {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last: Snapshot::default(),
+            sent: VecDeque::new(),
+            succeed: VecDeque::new(),
+            blocks: VecDeque::new(),
+        }
+    }
+
+    fn update(&mut self, snapshot: Snapshot, secs: f64) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let sent = snapshot.sent.saturating_sub(self.last.sent) as f64 / secs;
+        let succeed = snapshot.succeed.saturating_sub(self.last.succeed) as f64 / secs;
+        self.sent.push_back((elapsed, sent));
+        self.succeed.push_back((elapsed, succeed));
+        if self.sent.len() > WINDOW {
+            self.sent.pop_front();
+            self.succeed.pop_front();
+        }
+        if self.blocks.back().is_none_or(|(height, _)| snapshot.height > *height) {
+            self.blocks.push_back((snapshot.height, Instant::now()));
+            if self.blocks.len() > BLOCKS {
+                self.blocks.pop_front();
+            }
+        }
+        self.last = snapshot;
+    }
+
+    /// average seconds between the blocks seen
+    fn block_time(&self) -> Option<f64> {
+        let (first, since) = self.blocks.front()?;
+        let (last, until) = self.blocks.back()?;
+        (last > first).then(|| until.duration_since(*since).as_secs_f64() / (last - first) as f64)
+    }
+
+    /// tenths of seconds between the blocks seen, for the sparkline
+    fn block_intervals(&self) -> Vec<u64> {
+        self.blocks
+            .iter()
+            .zip(self.blocks.iter().skip(1))
+            .map(|((h0, t0), (h1, t1))| t1.duration_since(*t0).as_millis() as u64 / (h1 - h0).max(1) / 100)
+            .collect()
+    }
+}
+
+/// `true` if closed by Ctrl-C
+fn run<F>(title: &str, mut sample: F, stop: &AtomicBool) -> io::Result<bool>
+where
+    F: FnMut() -> Snapshot,
+{
+    restore_on_panic();
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let result = draw_loop(&mut terminal, title, &mut sample, stop);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    // back to the default hook
+    drop(std::panic::take_hook());
+    result
+}
+
+/// restore the terminal before a panic of any thread is reported, or the message is lost with the
+/// alternate screen and the shell is left in raw mode
+fn restore_on_panic() {
+    let report = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+        report(info);
+    }));
+}
+
+fn draw_loop<F>(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    title: &str,
+    sample: &mut F,
+    stop: &AtomicBool,
+) -> io::Result<bool>
+where
+    F: FnMut() -> Snapshot,
+{
+    let mut history = History::new();
+    let mut last_tick = Instant::now();
+    history.update(sample(), 1.0);
+    loop {
+        terminal.draw(|f| draw(f, title, &history))?;
+        if stop.load(Relaxed) {
+            return Ok(false);
+        }
+        let timeout = TICK.saturating_sub(last_tick.elapsed());
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('q'),
+                    ..
+                }) => return Ok(false),
+                // raw mode swallows the SIGINT
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                }) => return Ok(true),
+                _ => {}
+            }
+        }
+        if last_tick.elapsed() >= TICK {
+            let secs = last_tick.elapsed().as_secs_f64();
+            last_tick = Instant::now();
+            history.update(sample(), secs);
+        }
+    }
+}
+
+fn draw<B: Backend>(f: &mut Frame<B>, title: &str, history: &History) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Percentage(45), Constraint::Min(6)])
+        .split(f.size());
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+        .split(rows[1]);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(rows[2]);
+
+    draw_summary(f, rows[0], title, history);
+    draw_rates(f, middle[0], history);
+    draw_blocks(f, middle[1], history);
+    draw_errors(f, bottom[0], &history.last);
+    draw_keys(f, bottom[1], &history.last);
+}
+
+fn draw_summary<B: Backend>(f: &mut Frame<B>, area: Rect, title: &str, history: &History) {
+    let last = &history.last;
+    let done = last.succeed + last.failed;
+    let success = last.succeed as f64 / done.max(1) as f64 * 100.0;
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let text = vec![
+        Spans::from(vec![
+            Span::styled(format!("elapsed {:.0}s", history.start.elapsed().as_secs_f64()), bold),
+            Span::raw(format!(
+                "   sent {}   succeeded {}   failed {}   success {:.2}%",
+                last.sent, last.succeed, last.failed, success
+            )),
+        ]),
+        Spans::from(Span::raw(format!(
+            "q closes this view, the run goes on and logs to {}",
+            LOG_FILE
+        ))),
+    ];
+    let block = Block::default().borders(Borders::ALL).title(title.to_string());
+    f.render_widget(Paragraph::new(text).block(block), area);
+}
+
+fn draw_rates<B: Backend>(f: &mut Frame<B>, area: Rect, history: &History) {
+    let sent = history.sent.iter().copied().collect::<Vec<_>>();
+    let succeed = history.succeed.iter().copied().collect::<Vec<_>>();
+    let from = sent.first().map_or(0.0, |(t, _)| *t);
+    let to = sent.last().map_or(1.0, |(t, _)| *t).max(from + 1.0);
+    let top = sent
+        .iter()
+        .chain(succeed.iter())
+        .map(|(_, rate)| *rate)
+        .fold(1.0, f64::max)
+        * 1.1;
+    let datasets = vec![
+        Dataset::default()
+            .name("sent/s")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Cyan))
+            .data(&sent),
+        Dataset::default()
+            .name("succeeded/s")
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Green))
+            .data(&succeed),
+    ];
+    let chart = Chart::new(datasets)
+        .block(Block::default().borders(Borders::ALL).title("rates"))
+        .x_axis(Axis::default().bounds([from, to]).labels(vec![
+            Span::raw(format!("{:.0}s", from)),
+            Span::raw(format!("{:.0}s", to)),
+        ]))
+        .y_axis(
+            Axis::default()
+                .bounds([0.0, top])
+                .labels(vec![Span::raw("0"), Span::raw(format!("{:.0}", top))]),
+        );
+    f.render_widget(chart, area);
+}
+
+fn draw_blocks<B: Backend>(f: &mut Frame<B>, area: Rect, history: &History) {
+    let title = match history.block_time() {
+        Some(secs) => format!("height {}, block time {:.1}s", history.last.height, secs),
+        None => format!("height {}", history.last.height),
+    };
+    let intervals = history.block_intervals();
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .style(Style::default().fg(Color::Yellow))
+        .data(&intervals);
+    f.render_widget(sparkline, area);
+}
+
+fn draw_errors<B: Backend>(f: &mut Frame<B>, area: Rect, last: &Snapshot) {
+    let errors = last
+        .errors
+        .iter()
+        .map(|(category, count)| (category.as_str(), *count))
+        .collect::<Vec<_>>();
+    let chart = BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title("errors"))
+        .bar_width(8)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::Red))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Red))
+        .data(&errors);
+    f.render_widget(chart, area);
+}
+
+fn draw_keys<B: Backend>(f: &mut Frame<B>, area: Rect, last: &Snapshot) {
+    // the keys behind come first
+    let mut keys = last.keys.iter().collect::<Vec<_>>();
+    keys.sort_by_key(|(_, (sent, _))| *sent);
+    let rows = keys.iter().map(|(address, (sent, succeed))| {
+        let progress = match last.per_key {
+            0 => "-".to_string(),
+            total => format!("{:.1}%", *sent as f64 / total as f64 * 100.0),
+        };
+        Row::new(vec![
+            format!("{:?}", address),
+            sent.to_string(),
+            succeed.to_string(),
+            progress,
+        ])
+    });
+    let widths = [
+        Constraint::Length(44),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(8),
+    ];
+    let table = Table::new(rows)
+        .header(
+            Row::new(vec!["key", "sent", "succeeded", "progress"]).style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("source keys ({})", keys.len())),
+        )
+        .widths(&widths);
+    f.render_widget(table, area);
+}
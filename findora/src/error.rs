@@ -1,45 +1,186 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::Formatter;
+use web3::error::TransportError;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    CheckTx,
-    SyncTx,
-    SendErr,
+    CheckTx(RpcError),
+    SyncTx(RpcError),
+    /// the request didn't reach the node
+    SendErr(String),
     TxInternalErr(InternalError),
+    InsufficientFunds(RpcError),
+    GasTooLow(RpcError),
+    Underpriced(RpcError),
+    /// the request timed out
+    Timeout(String),
+    /// the node responded with a non-success HTTP status
+    Http(u16),
+    /// the node is rate limiting requests
+    LimitExceeded(RpcError),
+    /// an error response not classified yet
+    Rpc(RpcError),
     Io(std::io::Error),
     Db(redis::RedisError),
+    Json(serde_json::Error),
     NotSupport(String),
     Unknown(String),
 }
 
 #[derive(Debug)]
 pub enum InternalError {
-    InvalidNonce(String),
-    Other(String),
+    InvalidNonce(RpcError),
+    Other(RpcError),
+}
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// rejected by the rate limit of the node, EIP-1474
+const LIMIT_EXCEEDED: i64 = -32005;
+/// geth and most nodes reject a tx with a server error, the reason is in the message
+const SERVER_ERRORS: std::ops::RangeInclusive<i64> = -32099..=-32000;
+
+/// A JSON-RPC error response, as returned by the node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.data {
+            Some(data) => write!(f, "{} {}, data: {}", self.code, self.message, data),
+            None => write!(f, "{} {}", self.code, self.message),
+        }
+    }
+}
+
+impl RpcError {
+    /// the message and the data, where Findora puts the reason, the reason of a structured data is
+    /// in its `message`, `reason` or `log` field
+    fn text(&self) -> String {
+        let data = match &self.data {
+            Some(serde_json::Value::String(data)) => data.clone(),
+            Some(serde_json::Value::Object(data)) => ["message", "reason", "log"]
+                .iter()
+                .filter_map(|key| data.get(*key))
+                .map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Some(data) => data.to_string(),
+            None => return self.message.clone(),
+        };
+        format!("{} {}", self.message, data)
+    }
+
+    /// classify a rejected tx by the reason, the error is given back for other reasons
+    fn rejected(self) -> std::result::Result<Error, RpcError> {
+        let lower = self.text().to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+        if has(&["nonce too low", "nonce too high", "invalid nonce", "invalidnonce"]) {
+            Ok(Error::TxInternalErr(InternalError::InvalidNonce(self)))
+        } else if has(&["insufficient funds", "insufficient balance", "insufficientbalance"]) {
+            Ok(Error::InsufficientFunds(self))
+        } else if has(&["gas too low", "out of gas"]) {
+            Ok(Error::GasTooLow(self))
+        } else if has(&["underpriced", "gas price too low", "less than block base fee"]) {
+            Ok(Error::Underpriced(self))
+        } else if has(&["txpool is full", "mempool is full"]) {
+            Ok(Error::SyncTx(self))
+        } else {
+            Err(self)
+        }
+    }
+
+    /// classify a failure of Findora by the stage it failed at, the error is given back if not known
+    fn findora(self) -> std::result::Result<Error, RpcError> {
+        let text = self.text();
+        if text.contains("broadcast_tx_sync") {
+            Ok(Error::SyncTx(self))
+        } else if text.contains("Transaction check error") {
+            Ok(Error::CheckTx(self))
+        } else if text.contains("InternalError") {
+            Ok(Error::TxInternalErr(InternalError::Other(self)))
+        } else {
+            Err(self)
+        }
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Error::CheckTx => write!(f, "tx check failed"),
-            Error::SyncTx => write!(f, "tx not accepted by tendermint"),
-            Error::SendErr => write!(f, "tx not sent"),
+            Error::CheckTx(e) => write!(f, "tx check failed: {}", e),
+            Error::SyncTx(e) => write!(f, "tx not accepted by tendermint: {}", e),
+            Error::SendErr(e) => write!(f, "tx not sent: {}", e),
             Error::TxInternalErr(e) => write!(f, "Internal Error:: {:?}", e),
+            Error::InsufficientFunds(e) => write!(f, "insufficient funds: {}", e),
+            Error::GasTooLow(e) => write!(f, "gas too low: {}", e),
+            Error::Underpriced(e) => write!(f, "tx underpriced: {}", e),
+            Error::Timeout(e) => write!(f, "request timed out: {}", e),
+            Error::Http(status) => write!(f, "http status {}", status),
+            Error::LimitExceeded(e) => write!(f, "rate limited: {}", e),
+            Error::Rpc(e) => write!(f, "rpc error: {}", e),
             Error::Io(e) => write!(f, "Io error {:?}", e),
             Error::Db(e) => write!(f, "Database error {:?}", e),
+            Error::Json(e) => write!(f, "Json error {:?}", e),
             Error::NotSupport(e) => write!(f, "Not support: {}", e),
             Error::Unknown(e) => write!(f, "a unknown error happened: {}", e),
         }
     }
 }
 
+impl Error {
+    /// a short name to group errors by
+    pub fn category(&self) -> &'static str {
+        match self {
+            Error::CheckTx(_) => "check_tx",
+            Error::SyncTx(_) => "sync_tx",
+            Error::SendErr(_) => "send",
+            Error::TxInternalErr(InternalError::InvalidNonce(_)) => "invalid_nonce",
+            Error::TxInternalErr(InternalError::Other(_)) => "internal",
+            Error::InsufficientFunds(_) => "insufficient_funds",
+            Error::GasTooLow(_) => "gas_too_low",
+            Error::Underpriced(_) => "underpriced",
+            Error::Timeout(_) => "timeout",
+            Error::Http(_) => "http",
+            Error::LimitExceeded(_) => "limit_exceeded",
+            Error::Rpc(_) => "rpc",
+            Error::Io(_) => "io",
+            Error::Db(_) => "db",
+            Error::Json(_) => "json",
+            Error::NotSupport(_) => "not_support",
+            Error::Unknown(_) => "unknown",
+        }
+    }
+
+    /// the nonce of the tx doesn't match the account
+    pub fn is_invalid_nonce(&self) -> bool {
+        matches!(self, Error::TxInternalErr(InternalError::InvalidNonce(_)))
+    }
+
+    /// the node can't keep up, e.g. the mempool is full or the request timed out
+    pub fn is_back_pressure(&self) -> bool {
+        matches!(
+            self,
+            Error::SyncTx(_) | Error::Timeout(_) | Error::LimitExceeded(_) | Error::Http(429 | 503)
+        )
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Io(e) => Some(e),
             Error::Db(e) => Some(e),
+            Error::Json(e) => Some(e),
             _ => None,
         }
     }
@@ -56,3 +197,102 @@ impl From<redis::RedisError> for Error {
         Self::Db(e)
     }
 }
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<RpcError> for Error {
+    /// by the code first, the reason in the message only tells apart the errors of the same code
+    fn from(e: RpcError) -> Self {
+        match e.code {
+            LIMIT_EXCEEDED => Error::LimitExceeded(e),
+            METHOD_NOT_FOUND => Error::NotSupport(e.to_string()),
+            PARSE_ERROR | INVALID_REQUEST | INVALID_PARAMS => Error::Rpc(e),
+            code if SERVER_ERRORS.contains(&code) => e.rejected().unwrap_or_else(Error::Rpc),
+            // Findora reports the failures of check_tx and broadcast_tx_sync as internal errors, -32603,
+            // the stage is only in the message, so is the reason of an unknown code
+            _ => e.rejected().or_else(RpcError::findora).unwrap_or_else(Error::Rpc),
+        }
+    }
+}
+
+impl From<web3::Error> for Error {
+    fn from(e: web3::Error) -> Self {
+        match e {
+            web3::Error::Rpc(e) => RpcError {
+                code: e.code.code(),
+                message: e.message,
+                data: e.data,
+            }
+            .into(),
+            web3::Error::Transport(TransportError::Code(status)) => Error::Http(status),
+            web3::Error::Transport(TransportError::Message(msg)) => {
+                if msg.contains("timed out") || msg.contains("timeout") {
+                    Error::Timeout(msg)
+                } else {
+                    Error::SendErr(msg)
+                }
+            }
+            e => Error::Unknown(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(payload: &str) -> Error {
+        serde_json::from_str::<RpcError>(payload).unwrap().into()
+    }
+
+    #[test]
+    fn server_errors_by_reason() {
+        let nonce = classify(r#"{"code":-32000,"message":"nonce too low"}"#);
+        assert!(nonce.is_invalid_nonce());
+        let funds = classify(r#"{"code":-32000,"message":"insufficient funds for gas * price + value"}"#);
+        assert_eq!(funds.category(), "insufficient_funds");
+        let gas = classify(r#"{"code":-32000,"message":"intrinsic gas too low"}"#);
+        assert_eq!(gas.category(), "gas_too_low");
+        let price = classify(r#"{"code":-32000,"message":"replacement transaction underpriced"}"#);
+        assert_eq!(price.category(), "underpriced");
+        let full = classify(r#"{"code":-32000,"message":"txpool is full"}"#);
+        assert!(full.is_back_pressure());
+        let known = classify(r#"{"code":-32000,"message":"already known"}"#);
+        assert_eq!(known.category(), "rpc");
+    }
+
+    #[test]
+    fn findora_errors_by_stage() {
+        let sync = classify(
+            r#"{"code":-32603,"message":"send_raw_transaction: broadcast_tx_sync error: mempool is full: number of txs 5000 (max: 5000)"}"#,
+        );
+        assert_eq!(sync.category(), "sync_tx");
+        let check = classify(r#"{"code":-32603,"message":"Transaction check error: CheckTx failed, code: 1"}"#);
+        assert_eq!(check.category(), "check_tx");
+        let nonce =
+            classify(r#"{"code":-32603,"message":"InternalError","data":"InvalidNonce, expected: 12, actual: 10"}"#);
+        assert!(nonce.is_invalid_nonce());
+        let nonce =
+            classify(r#"{"code":-32603,"message":"Transaction check error","data":{"code":1,"log":"InvalidNonce"}}"#);
+        assert!(nonce.is_invalid_nonce());
+        let other = classify(r#"{"code":-32603,"message":"InternalError","data":"evm exit: OutOfFund"}"#);
+        assert_eq!(other.category(), "internal");
+    }
+
+    #[test]
+    fn by_code() {
+        let limited = classify(r#"{"code":-32005,"message":"limit exceeded"}"#);
+        assert!(limited.is_back_pressure());
+        let method = classify(r#"{"code":-32601,"message":"Method not found"}"#);
+        assert_eq!(method.category(), "not_support");
+        // the message of an invalid request isn't a rejection of the tx
+        let params = classify(r#"{"code":-32602,"message":"invalid nonce in params"}"#);
+        assert_eq!(params.category(), "rpc");
+        let reverted = classify(r#"{"code":3,"message":"execution reverted","data":"0x08c379a0"}"#);
+        assert_eq!(reverted.category(), "rpc");
+    }
+}
@@ -0,0 +1,137 @@
+use crate::load::{Sender, Source};
+use feth::error::{Error, Result};
+use futures::StreamExt;
+use log::error;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
+use web3::types::{H256, U256};
+
+/// sends needed before the error rate is checked
+const MIN_SAMPLES: u64 = 100;
+/// interval of checking the balances of source keys
+const BALANCE_INTERVAL: Duration = Duration::from_secs(30);
+/// balance queries in flight at the same time
+const BALANCE_QUERIES: usize = 16;
+
+/// Thresholds to stop a load test early, unset ones are not checked.
+///
+/// Consecutive `SyncTx` errors are counted by the senders, the others are checked by `watch`.
+/// Once a threshold is crossed the guard trips, and the test stops sending.
+#[derive(Debug, Default)]
+pub(crate) struct Guard {
+    /// percent of failed sends
+    pub max_error_rate: Option<f64>,
+    pub max_sync_errors: Option<u64>,
+    /// lowest balance of a source key
+    pub min_balance: Option<U256>,
+    /// longest time without a new block
+    pub max_stall: Option<Duration>,
+    sync_errors: AtomicU64,
+    reason: Mutex<Option<String>>,
+}
+
+impl Guard {
+    pub fn new(
+        max_error_rate: Option<f64>,
+        max_sync_errors: Option<u64>,
+        min_balance: Option<U256>,
+        max_stall: Option<Duration>,
+    ) -> Self {
+        Self {
+            max_error_rate,
+            max_sync_errors,
+            min_balance,
+            max_stall,
+            ..Default::default()
+        }
+    }
+
+    /// if any threshold needs to be watched
+    pub fn needs_watch(&self) -> bool {
+        self.max_error_rate.is_some() || self.min_balance.is_some() || self.max_stall.is_some()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.reason.lock().unwrap().is_some()
+    }
+
+    /// why the guard tripped
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+
+    /// stop the test, only the first reason is kept
+    pub fn trip(&self, reason: String) {
+        let mut current = self.reason.lock().unwrap();
+        if current.is_none() {
+            error!("Aborting test: {}", reason);
+            *current = Some(reason);
+        }
+    }
+
+    /// count the result of a send, `None` if no nonce was available
+    pub fn record(&self, result: Option<&Result<H256>>) {
+        if let Some(Err(Error::SyncTx(_))) = result {
+            let errors = self.sync_errors.fetch_add(1, Relaxed) + 1;
+            if matches!(self.max_sync_errors, Some(max) if errors >= max) {
+                self.trip(format!("{} consecutive SyncTx errors", errors));
+            }
+        } else {
+            self.sync_errors.store(0, Relaxed);
+        }
+    }
+
+    /// Check the error rate of `senders`, the height of the node and the balances of `sources`
+    /// until the guard trips or the task is aborted.
+    pub async fn watch(self: Arc<Self>, senders: Arc<Vec<Sender>>, sources: Arc<Vec<Source>>) {
+        let client = senders.first().unwrap().client.clone();
+        let mut height = client.block_number_async().await.unwrap_or_default();
+        let mut last_block = Instant::now();
+        let mut last_balance: Option<Instant> = None;
+        while !self.is_tripped() {
+            match client.wait_block_async(height, Duration::from_secs(1)).await {
+                Some(current) => {
+                    height = current;
+                    last_block = Instant::now();
+                }
+                None => {
+                    if matches!(self.max_stall, Some(max) if last_block.elapsed() > max) {
+                        self.trip(format!("no new block after {} in {:?}", height, last_block.elapsed()));
+                    }
+                }
+            }
+            if let Some(max) = self.max_error_rate {
+                let succeed = senders.iter().map(|s| s.succeed.load(Relaxed)).sum::<u64>();
+                let failed = senders.iter().map(|s| s.failed.load(Relaxed)).sum::<u64>();
+                let rate = failed as f64 / (succeed + failed).max(1) as f64 * 100.0;
+                if succeed + failed >= MIN_SAMPLES && rate > max {
+                    self.trip(format!("error rate {:.2}% of {} sends", rate, succeed + failed));
+                }
+            }
+            if let Some(min) = self.min_balance {
+                if last_balance.is_none_or(|t| t.elapsed() >= BALANCE_INTERVAL) {
+                    last_balance = Some(Instant::now());
+                    let addresses = sources.iter().map(|(_, address, _)| *address).collect::<Vec<_>>();
+                    let balances = futures::stream::iter(addresses)
+                        .map(|address| {
+                            let client = client.clone();
+                            async move { client.try_balance_async(address, None).await.map(|b| (b, address)) }
+                        })
+                        .buffer_unordered(BALANCE_QUERIES)
+                        .collect::<Vec<_>>()
+                        .await;
+                    let lowest = balances.into_iter().filter_map(|balance| balance.ok()).min();
+                    if let Some((balance, address)) = lowest.filter(|(balance, _)| *balance < min) {
+                        self.trip(format!("balance of {:?} is {}, below {}", address, balance, min));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::{Error, Result};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::watch;
+use web3::{transports::WebSocket, types::U64, Web3};
+
+/// Heights of new blocks, pushed by a `newHeads` subscription over WebSocket.
+///
+/// Waiters wake up as soon as a block arrives instead of polling `eth_blockNumber`.
+/// Once the subscription ends, `is_alive` turns false and callers should poll again.
+#[derive(Debug, Clone)]
+pub struct NewHeads {
+    rx: watch::Receiver<U64>,
+}
+
+impl NewHeads {
+    /// subscribe to `newHeads` of `url`, must be called inside a tokio runtime
+    pub async fn subscribe(url: &str) -> Result<Self> {
+        let transport = WebSocket::new(url)
+            .await
+            .map_err(|e| Error::Unknown(format!("connect {}: {}", url, e)))?;
+        let web3 = Web3::new(transport);
+        let mut heads = web3
+            .eth_subscribe()
+            .subscribe_new_heads()
+            .await
+            .map_err(|e| Error::Unknown(format!("subscribe newHeads: {}", e)))?;
+        let (tx, rx) = watch::channel(U64::zero());
+        tokio::spawn(async move {
+            // the transport lives as long as the subscription
+            let _web3 = web3;
+            while let Some(head) = heads.next().await {
+                match head {
+                    Ok(head) => {
+                        if let Some(number) = head.number {
+                            if tx.send(number).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("newHeads subscription ended: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self { rx })
+    }
+
+    /// the latest height received, zero before the first block
+    pub fn height(&self) -> U64 {
+        *self.rx.borrow()
+    }
+
+    /// the subscription is still running
+    pub fn is_alive(&self) -> bool {
+        self.rx.has_changed().is_ok()
+    }
+
+    /// wait for a block higher than `height`, returns its height or `None` on timeout
+    pub async fn wait_after(&self, height: U64, timeout: Duration) -> Option<U64> {
+        let mut rx = self.rx.clone();
+        let wait = async {
+            loop {
+                let latest = *rx.borrow_and_update();
+                if latest > height {
+                    break Some(latest);
+                }
+                if rx.changed().await.is_err() {
+                    break None;
+                }
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.ok().flatten()
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{error::Error, error::Result, one_eth_key, KeyPair};
+use bip0039::{Count, Language, Mnemonic};
+use bip32::{ChildNumber, DerivationPath, XPrv};
+use libsecp256k1::{PublicKey, SecretKey};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    ops::Range,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use web3::types::{H160, H256};
+
+/// the account path of a key set, keys are its children
+pub const ACCOUNT_PATH: &str = "m/44'/60'/0'/0";
+
+/// A range of keys derived from one seed mnemonic, at `m/44'/60'/0'/0/i`.
+///
+/// Only the file of the mnemonic is referenced, no private key is saved along with the set,
+/// so the keys can be derived again as long as the mnemonic is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeySet {
+    /// file of the seed mnemonic
+    pub seed: PathBuf,
+    pub start: u32,
+    pub count: u32,
+}
+
+impl KeySet {
+    /// `count` keys of the mnemonic in `seed` from `start`, a new mnemonic is saved there if the file
+    /// doesn't exist
+    pub fn create(seed: &Path, start: u32, count: u32) -> Result<Self> {
+        // only the owner can read a new mnemonic, an existing one is kept
+        match OpenOptions::new().write(true).create_new(true).mode(0o600).open(seed) {
+            Ok(mut file) => {
+                let mnemonic = Mnemonic::generate_in(Language::English, Count::Words24);
+                file.write_all(mnemonic.phrase().as_bytes())?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e.into()),
+        }
+        // the key file may be used from another directory
+        Ok(Self {
+            seed: seed.canonicalize()?,
+            start,
+            count,
+        })
+    }
+
+    /// the set is derived from the mnemonic in `seed`
+    pub fn is_of(&self, seed: &Path) -> bool {
+        matches!((self.seed.canonicalize(), seed.canonicalize()), (Ok(a), Ok(b)) if a == b)
+    }
+
+    /// the index after the last key
+    pub fn end(&self) -> u32 {
+        self.start + self.count
+    }
+
+    pub fn derive(&self) -> Result<Vec<KeyPair>> {
+        let phrase = std::fs::read_to_string(&self.seed)?;
+        derive_keys(phrase.trim(), self.start..self.start + self.count)
+    }
+}
+
+/// Derive the keys of `indices` from `phrase`, the seed and the account key are derived only once
+pub fn derive_keys(phrase: &str, indices: Range<u32>) -> Result<Vec<KeyPair>> {
+    let mnemonic = Mnemonic::from_phrase(phrase).map_err(|e| Error::Unknown(format!("invalid mnemonic: {}", e)))?;
+    let seed = mnemonic.to_seed("");
+    let account = XPrv::derive_from_path(&seed, &DerivationPath::from_str(ACCOUNT_PATH).unwrap())
+        .map_err(|e| Error::Unknown(format!("derive {}: {}", ACCOUNT_PATH, e)))?;
+    indices
+        .into_par_iter()
+        .map(|index| {
+            let child = ChildNumber::new(index, false)
+                .and_then(|child| account.derive_child(child))
+                .map_err(|e| Error::Unknown(format!("derive {}/{}: {}", ACCOUNT_PATH, index, e)))?;
+            Ok(key_pair(&child))
+        })
+        .collect()
+}
+
+/// the address and the private key of an extended key
+pub(crate) fn key_pair(ext: &XPrv) -> KeyPair {
+    let secret = SecretKey::parse_slice(&ext.to_bytes()).unwrap();
+    let public = PublicKey::from_secret_key(&secret);
+
+    let mut res = [0u8; 64];
+    res.copy_from_slice(&public.serialize()[1..65]);
+    let public = H160::from(H256::from_slice(Keccak256::digest(&res).as_slice()));
+
+    KeyPair {
+        address: eth_checksum::checksum(&format!("{:?}", public)),
+        private: hex::encode(secret.serialize()),
+    }
+}
+
+/// A source key file, either a key set or the key pairs themselves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyFile {
+    Set(KeySet),
+    Keys(Vec<KeyPair>),
+}
+
+impl KeyFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            KeyFile::Set(set) => set.count as usize,
+            KeyFile::Keys(keys) => keys.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// grow to `count` keys, random ones are added to key pairs
+    pub fn extend(&mut self, count: usize) {
+        match self {
+            KeyFile::Set(set) => set.count = set.count.max(count as u32),
+            KeyFile::Keys(keys) => {
+                if count > keys.len() {
+                    keys.resize_with(count, one_eth_key)
+                }
+            }
+        }
+    }
+
+    /// split into at most `parts` contiguous shards, a key set into ranges of the same mnemonic
+    pub fn split(&self, parts: usize) -> Vec<KeyFile> {
+        let size = self.len().div_ceil(parts.max(1)).max(1);
+        match self {
+            KeyFile::Set(set) => (0..set.count)
+                .step_by(size)
+                .map(|offset| {
+                    KeyFile::Set(KeySet {
+                        seed: set.seed.clone(),
+                        start: set.start + offset,
+                        count: (size as u32).min(set.count - offset),
+                    })
+                })
+                .collect(),
+            KeyFile::Keys(keys) => keys.chunks(size).map(|keys| KeyFile::Keys(keys.to_vec())).collect(),
+        }
+    }
+
+    pub fn keys(self) -> Result<Vec<KeyPair>> {
+        match self {
+            KeyFile::Set(set) => set.derive(),
+            KeyFile::Keys(keys) => Ok(keys),
+        }
+    }
+}
+
+/// load the key pairs of a source key file
+pub fn load_keys<P: AsRef<Path>>(path: P) -> Result<Vec<KeyPair>> {
+    KeyFile::load(path)?.keys()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str =
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn derive_bip44_keys() {
+        let keys = derive_keys(PHRASE, 0..2).unwrap();
+        let addresses = keys.iter().map(|key| key.address.to_lowercase()).collect::<Vec<_>>();
+        assert_eq!(
+            addresses,
+            [
+                "0x9858effd232b4033e47d90003d41ec34ecaeda94",
+                "0x6fac4d18c912343bf86fa7049364dd4e424ab9c0",
+            ]
+        );
+    }
+
+    #[test]
+    fn derive_a_range() {
+        let keys = derive_keys(PHRASE, 1..2).unwrap();
+        assert_eq!(
+            keys[0].address.to_lowercase(),
+            "0x6fac4d18c912343bf86fa7049364dd4e424ab9c0"
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::TestClient;
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use web3::types::{BlockId, BlockNumber, H256, U64};
+
+/// highest trackable latency, milliseconds
+pub const MAX_LATENCY: u64 = 3_600_000;
+/// name of the signing stage, it's recorded in microseconds as signing takes less than a millisecond
+pub const SIGN_STAGE: &str = "sign_us";
+
+/// Percentiles of a latency histogram, milliseconds except `SIGN_STAGE`
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl From<&Histogram<u64>> for LatencySummary {
+    fn from(h: &Histogram<u64>) -> Self {
+        Self {
+            count: h.len(),
+            p50: h.value_at_quantile(0.5),
+            p90: h.value_at_quantile(0.9),
+            p99: h.value_at_quantile(0.99),
+            max: h.max(),
+        }
+    }
+}
+
+/// Latency of every sent transaction, from signing to
+/// * signed, signing finished, in microseconds
+/// * acked, `send_raw_transaction` returned the tx hash
+/// * included, the tx was found in a new block
+#[derive(Debug)]
+pub struct LatencyTracker {
+    pending: Mutex<HashMap<H256, Instant>>,
+    inclusion: Mutex<HashMap<H256, Duration>>,
+    keep_inclusion: bool,
+    signed: Mutex<Histogram<u64>>,
+    acked: Mutex<Histogram<u64>>,
+    included: Mutex<Histogram<u64>>,
+    finished: AtomicBool,
+    /// the last block checked
+    height: AtomicU64,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        let histogram = || Mutex::new(Histogram::new_with_bounds(1, MAX_LATENCY, 3).unwrap());
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            inclusion: Mutex::new(HashMap::new()),
+            keep_inclusion: true,
+            signed: Mutex::new(Histogram::new_with_bounds(1, MAX_LATENCY * 1000, 3).unwrap()),
+            acked: histogram(),
+            included: histogram(),
+            finished: AtomicBool::new(false),
+            height: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyTracker {
+    /// don't keep the inclusion latency of every tx, for long running tests
+    pub fn without_inclusion(mut self) -> Self {
+        self.keep_inclusion = false;
+        self
+    }
+
+    pub fn signed(&self, elapsed: Duration) {
+        self.signed
+            .lock()
+            .unwrap()
+            .saturating_record(elapsed.as_micros() as u64);
+    }
+
+    /// `start` is the time signing started, the tx will be tracked until included
+    pub fn acked(&self, hash: H256, start: Instant) {
+        self.acked
+            .lock()
+            .unwrap()
+            .saturating_record(start.elapsed().as_millis() as u64);
+        self.pending.lock().unwrap().insert(hash, start);
+    }
+
+    /// record the tracked txs in a block, returns the number of them
+    pub fn included(&self, hashes: &[H256]) -> usize {
+        let now = Instant::now();
+        let mut pending = self.pending.lock().unwrap();
+        let mut inclusion = self.inclusion.lock().unwrap();
+        let mut included = self.included.lock().unwrap();
+        hashes
+            .iter()
+            .filter_map(|hash| pending.remove(hash).map(|start| (hash, now.duration_since(start))))
+            .map(|(hash, latency)| {
+                included.saturating_record(latency.as_millis() as u64);
+                if self.keep_inclusion {
+                    inclusion.insert(*hash, latency);
+                }
+            })
+            .count()
+    }
+
+    /// latency from signing to inclusion of a tx
+    pub fn inclusion_of(&self, hash: &H256) -> Option<Duration> {
+        self.inclusion.lock().unwrap().get(hash).copied()
+    }
+
+    /// stop tracking the txs sent more than `max_age` ago, returns the number of them
+    pub fn evict(&self, max_age: Duration) -> usize {
+        let mut pending = self.pending.lock().unwrap();
+        let before = pending.len();
+        pending.retain(|_, start| start.elapsed() <= max_age);
+        before - pending.len()
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    /// no more txs will be sent
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+
+    /// the last block checked by `watch_blocks`
+    pub fn height(&self) -> u64 {
+        self.height.load(Ordering::Relaxed)
+    }
+
+    /// copies of the signed, acked and included histograms
+    pub fn histograms(&self) -> Vec<(&'static str, Histogram<u64>)> {
+        vec![
+            (SIGN_STAGE, self.signed.lock().unwrap().clone()),
+            ("ack", self.acked.lock().unwrap().clone()),
+            ("inclusion", self.included.lock().unwrap().clone()),
+        ]
+    }
+
+    /// summaries of signed, acked and included latency
+    pub fn summary(&self) -> Vec<(&'static str, LatencySummary)> {
+        vec![
+            (SIGN_STAGE, LatencySummary::from(&*self.signed.lock().unwrap())),
+            ("ack", LatencySummary::from(&*self.acked.lock().unwrap())),
+            ("inclusion", LatencySummary::from(&*self.included.lock().unwrap())),
+        ]
+    }
+
+    /// Follow new blocks since `start_height` and record the tracked txs in them, blocks are
+    /// pushed by the `newHeads` subscription of `client` if any.
+    ///
+    /// Txs not included in `timeout` since signing are no longer tracked until `finish`, so a long
+    /// run doesn't keep every dropped tx. Returns after `finish` once all tracked txs are included, or no tx is
+    /// included in `timeout`.
+    pub async fn watch_blocks(&self, client: &TestClient, start_height: U64, timeout: Duration) {
+        let mut last_height = start_height;
+        let mut last_included = Instant::now();
+        let mut draining = false;
+        loop {
+            let mut caught_up = true;
+            if let Some(height) = client.wait_block_async(last_height, Duration::from_millis(500)).await {
+                while last_height < height {
+                    let id = BlockId::Number(BlockNumber::Number(last_height + 1));
+                    match client.block_with_tx_hashes_async(id).await {
+                        Some(block) => {
+                            if self.included(&block.transactions) > 0 {
+                                last_included = Instant::now();
+                            }
+                            last_height += U64::one();
+                            self.height.store(last_height.as_u64(), Ordering::Relaxed);
+                        }
+                        None => {
+                            caught_up = false;
+                            tokio::time::sleep(Duration::from_millis(500)).await;
+                            break;
+                        }
+                    }
+                }
+            }
+            // only once caught up, the txs may be in the blocks not checked yet
+            if caught_up && !self.is_finished() {
+                let evicted = self.evict(timeout);
+                if evicted > 0 {
+                    log::debug!("{} txs not included in {:?}, no longer tracked", evicted, timeout);
+                }
+            }
+            if self.is_finished() {
+                if !draining {
+                    draining = true;
+                    last_included = Instant::now();
+                }
+                if self.pending() == 0 {
+                    break;
+                }
+                if last_included.elapsed() > timeout {
+                    log::warn!("{} txs not included in {:?}", self.pending(), timeout);
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,39 +1,48 @@
+pub mod concurrency;
+pub mod contract;
+pub mod corpus;
 pub mod error;
+pub mod heads;
+pub mod keys;
+pub mod latency;
+pub mod nonce;
+pub mod progress;
+pub mod report;
 pub mod utils;
 
 use crate::{
-    error::{Error, InternalError, Result},
+    concurrency::Concurrency,
+    contract::{Erc20, DEPLOY_GAS, TRANSFER_GAS},
+    error::{Error, Result},
+    heads::NewHeads,
+    progress::Progress,
     utils::extract_keypair_from_file,
 };
 use bip0039::{Count, Language, Mnemonic};
 use bip32::{DerivationPath, XPrv};
-use libsecp256k1::{PublicKey, SecretKey};
 use log::{debug, error, info, warn};
 use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
-use sha3::{Digest, Keccak256};
 use std::{
     cell::RefCell,
-    error::Error as StdError,
     ops::AddAssign,
     str::FromStr,
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use tokio::runtime::Runtime;
 use web3::{
     transports::Http,
     types::{
-        Address, Block, BlockId, BlockNumber, Bytes, Transaction, TransactionId, TransactionParameters,
-        TransactionReceipt, H160, H256, U256, U64,
+        AccessListItem, Address, Block, BlockId, BlockNumber, Bytes, CallRequest, SignedTransaction, Transaction,
+        TransactionId, TransactionParameters, TransactionReceipt, H160, H256, U256, U64,
     },
 };
 
 const FRC20_ADDRESS: u64 = 0x1000;
 pub const BLOCK_TIME: u64 = 16;
+/// gas limit of a plain value transfer
+pub const VALUE_TRANSFER_GAS: u64 = 21_000;
 
 //const WEB3_SRV: &str = "http://127.0.0.1:8545";
 //const WEB3_SRV: &str = "http://18.236.205.22:8545";
@@ -51,18 +60,12 @@ pub fn one_eth_key() -> KeyPair {
     let mnemonic = Mnemonic::generate_in(Language::English, Count::Words12);
     let bs = mnemonic.to_seed("");
     let ext = XPrv::derive_from_path(&bs, &DerivationPath::from_str("m/44'/60'/0'/0/0").unwrap()).unwrap();
+    keys::key_pair(&ext)
+}
 
-    let secret = SecretKey::parse_slice(&ext.to_bytes()).unwrap();
-    let public = PublicKey::from_secret_key(&secret);
-
-    let mut res = [0u8; 64];
-    res.copy_from_slice(&public.serialize()[1..65]);
-    let public = H160::from(H256::from_slice(Keccak256::digest(&res).as_slice()));
-
-    KeyPair {
-        address: eth_checksum::checksum(&format!("{:?}", public)),
-        private: hex::encode(secret.serialize()),
-    }
+/// a random address to send to, much cheaper than `one_eth_key` when the key isn't needed
+pub fn random_address() -> Address {
+    Address::random()
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -82,6 +85,59 @@ pub struct TransferMetrics {
     pub txs: Vec<TxMetric>,
 }
 
+/// Result of draining a source key
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct SweepMetric {
+    pub from: Address,
+    pub to: Address,
+    pub balance: U256,
+    pub amount: U256,       // balance minus fee, zero if the fee isn't covered
+    pub hash: Option<H256>, // Tx hash
+    pub status: u64,        // 1 - success, 0 - nothing to sweep, other - fail
+    pub error: Option<String>,
+}
+
+/// Type and fees of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxFee {
+    /// legacy transaction
+    Legacy { gas_price: U256 },
+    /// EIP-2930 transaction, the callee is put in its access list
+    AccessList { gas_price: U256 },
+    /// EIP-1559 transaction
+    DynamicFee { max_fee: U256, priority_fee: U256 },
+}
+
+impl TxFee {
+    /// set the type, fees and access list of `tx`, `tx.to` should be set already unless it deploys
+    pub fn apply(&self, tx: &mut TransactionParameters) {
+        match *self {
+            TxFee::Legacy { gas_price } => {
+                tx.gas_price = Some(gas_price);
+            }
+            TxFee::AccessList { gas_price } => {
+                tx.transaction_type = Some(U64::from(1));
+                tx.gas_price = Some(gas_price);
+                // a deployment has no address to list yet
+                tx.access_list = Some(
+                    tx.to
+                        .map(|address| AccessListItem {
+                            address,
+                            storage_keys: vec![],
+                        })
+                        .into_iter()
+                        .collect(),
+                );
+            }
+            TxFee::DynamicFee { max_fee, priority_fee } => {
+                tx.transaction_type = Some(U64::from(2));
+                tx.max_fee_per_gas = Some(max_fee);
+                tx.max_priority_fee_per_gas = Some(priority_fee);
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TestClient {
     pub web3: Arc<web3::Web3<Http>>,
@@ -89,11 +145,15 @@ pub struct TestClient {
     pub accounts: Arc<web3::api::Accounts<Http>>,
     pub root_sk: secp256k1::SecretKey,
     pub root_addr: Address,
-    pub overflow_flag: AtomicUsize,
-    rt: Runtime,
+    /// in-flight sends of this endpoint
+    pub concurrency: Concurrency,
+    /// progress of `distribution`
+    pub progress: Arc<Progress>,
+    heads: Option<NewHeads>,
+    rt: Arc<Runtime>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkInfo {
     pub chain_id: U256,
     pub block_number: U64,
@@ -103,6 +163,15 @@ pub struct NetworkInfo {
 
 impl TestClient {
     pub fn setup(url: Option<String>, timeout: Option<u64>) -> Self {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        Self::setup_with_runtime(url, timeout, Arc::new(rt))
+    }
+
+    /// setup a client driven by a shared multi-threaded runtime
+    pub fn setup_with_runtime(url: Option<String>, timeout: Option<u64>, rt: Arc<Runtime>) -> Self {
         let client = Client::builder()
             .timeout(Duration::from_secs(timeout.unwrap_or(3)))
             .build()
@@ -113,10 +182,6 @@ impl TestClient {
         let eth = Arc::new(web3.eth());
         let accounts = Arc::new(web3.accounts());
         let (root_sk, root_addr) = extract_keypair_from_file(".secret");
-        let rt = tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .unwrap();
 
         Self {
             web3,
@@ -125,16 +190,73 @@ impl TestClient {
             root_sk,
             root_addr,
             rt,
-            overflow_flag: AtomicUsize::from(0),
+            concurrency: Concurrency::new(usize::MAX),
+            progress: Arc::default(),
+            heads: None,
+        }
+    }
+
+    /// at most `max` sends in flight, fewer while the node pushes back
+    pub fn with_max_in_flight(mut self, max: usize) -> Self {
+        self.concurrency = Concurrency::new(max);
+        self
+    }
+
+    /// follow new blocks with a `newHeads` subscription instead of polling
+    pub fn with_new_heads(mut self, heads: Option<NewHeads>) -> Self {
+        self.heads = heads;
+        self
+    }
+
+    pub fn new_heads(&self) -> Option<NewHeads> {
+        self.heads.clone()
+    }
+
+    pub fn network_info(&self) -> NetworkInfo {
+        NetworkInfo {
+            chain_id: self.chain_id().unwrap_or_default(),
+            block_number: self.block_number().unwrap_or_default(),
+            gas_price: self.gas_price().unwrap_or_default(),
+            frc20_code: self.frc20_code(),
         }
     }
 
+    /// runtime driving this client, async APIs should be spawned on it
+    pub fn runtime(&self) -> Arc<Runtime> {
+        self.rt.clone()
+    }
+
     pub fn chain_id(&self) -> Option<U256> {
-        self.rt.block_on(self.eth.chain_id()).ok()
+        self.rt.block_on(self.chain_id_async())
+    }
+
+    pub async fn chain_id_async(&self) -> Option<U256> {
+        self.eth.chain_id().await.ok()
     }
 
     pub fn block_number(&self) -> Option<U64> {
-        self.rt.block_on(self.eth.block_number()).ok()
+        self.rt.block_on(self.block_number_async())
+    }
+
+    pub async fn block_number_async(&self) -> Option<U64> {
+        self.eth.block_number().await.ok()
+    }
+
+    /// Wait for a block higher than `height`, returns the latest height if there is one.
+    ///
+    /// With a live `newHeads` subscription it returns once the block arrives or `timeout` expires,
+    /// otherwise the height is polled and it sleeps `timeout` if there's no new block.
+    pub async fn wait_block_async(&self, height: U64, timeout: Duration) -> Option<U64> {
+        if let Some(heads) = self.heads.as_ref().filter(|heads| heads.is_alive()) {
+            return heads.wait_after(height, timeout).await;
+        }
+        match self.block_number_async().await {
+            Some(latest) if latest > height => Some(latest),
+            _ => {
+                tokio::time::sleep(timeout).await;
+                None
+            }
+        }
     }
 
     pub fn current_block(&self) -> Option<Block<H256>> {
@@ -147,6 +269,10 @@ impl TestClient {
         self.block_with_tx_hashes_inner(id, None, None)
     }
 
+    pub async fn block_with_tx_hashes_async(&self, id: BlockId) -> Option<Block<H256>> {
+        self.eth.block(id).await.unwrap_or_default()
+    }
+
     pub fn block_with_tx_hashes_inner(
         &self,
         id: BlockId,
@@ -169,7 +295,11 @@ impl TestClient {
     }
 
     pub fn nonce(&self, from: Address, block: Option<BlockNumber>) -> Option<U256> {
-        self.rt.block_on(self.eth.transaction_count(from, block)).ok()
+        self.rt.block_on(self.nonce_async(from, block))
+    }
+
+    pub async fn nonce_async(&self, from: Address, block: Option<BlockNumber>) -> Option<U256> {
+        self.eth.transaction_count(from, block).await.ok()
     }
 
     pub fn pending_nonce(&self, from: Address) -> Option<U256> {
@@ -177,17 +307,27 @@ impl TestClient {
     }
 
     pub fn pending_nonce_inner(&self, from: Address, interval: Option<u64>, times: Option<u64>) -> Option<U256> {
+        self.rt.block_on(self.pending_nonce_inner_async(from, interval, times))
+    }
+
+    pub async fn pending_nonce_async(&self, from: Address) -> Option<U256> {
+        self.pending_nonce_inner_async(from, Some(3), None).await
+    }
+
+    pub async fn pending_nonce_inner_async(
+        &self,
+        from: Address,
+        interval: Option<u64>,
+        times: Option<u64>,
+    ) -> Option<U256> {
         let interval = interval.unwrap_or(5);
         let mut tries = 1u64;
         loop {
-            match self
-                .rt
-                .block_on(self.eth.transaction_count(from, Some(BlockNumber::Pending)))
-            {
+            match self.eth.transaction_count(from, Some(BlockNumber::Pending)).await {
                 Ok(nonce) => break Some(nonce),
                 Err(e) => error!("failed to get nonce, tries {}, {:?}", tries, e),
             }
-            std::thread::sleep(Duration::from_secs(interval));
+            tokio::time::sleep(Duration::from_secs(interval)).await;
             if times == Some(tries) || times == Some(0u64) {
                 break None;
             }
@@ -196,13 +336,19 @@ impl TestClient {
     }
 
     pub fn gas_price(&self) -> Option<U256> {
-        self.rt.block_on(self.eth.gas_price()).ok()
+        self.rt.block_on(self.gas_price_async())
+    }
+
+    pub async fn gas_price_async(&self) -> Option<U256> {
+        self.eth.gas_price().await.ok()
     }
 
     pub fn frc20_code(&self) -> Option<Bytes> {
-        self.rt
-            .block_on(self.eth.code(H160::from_low_u64_be(FRC20_ADDRESS), None))
-            .ok()
+        self.rt.block_on(self.frc20_code_async())
+    }
+
+    pub async fn frc20_code_async(&self) -> Option<Bytes> {
+        self.eth.code(H160::from_low_u64_be(FRC20_ADDRESS), None).await.ok()
     }
 
     #[allow(unused)]
@@ -211,7 +357,11 @@ impl TestClient {
     }
 
     pub fn transaction_receipt(&self, hash: H256) -> Option<TransactionReceipt> {
-        self.rt.block_on(self.eth.transaction_receipt(hash)).unwrap_or_default()
+        self.rt.block_on(self.transaction_receipt_async(hash))
+    }
+
+    pub async fn transaction_receipt_async(&self, hash: H256) -> Option<TransactionReceipt> {
+        self.eth.transaction_receipt(hash).await.unwrap_or_default()
     }
 
     #[allow(unused)]
@@ -220,57 +370,37 @@ impl TestClient {
     }
 
     pub fn balance(&self, address: Address, number: Option<BlockNumber>) -> U256 {
-        self.rt.block_on(self.eth.balance(address, number)).unwrap_or_default()
+        self.rt.block_on(self.balance_async(address, number))
     }
 
+    pub async fn balance_async(&self, address: Address, number: Option<BlockNumber>) -> U256 {
+        self.try_balance_async(address, number).await.unwrap_or_default()
+    }
+
+    pub async fn try_balance_async(&self, address: Address, number: Option<BlockNumber>) -> Result<U256> {
+        self.eth.balance(address, number).await.map_err(Error::from)
+    }
+
+    /// Wait up to `interval * times` for the receipt of `hash`, returns the waited seconds and the receipt.
+    ///
+    /// The receipt is checked on every new block with a `newHeads` subscription, or every `interval` otherwise.
     pub fn wait_for_tx_receipt(&self, hash: H256, interval: Duration, times: u64) -> (u64, Option<TransactionReceipt>) {
-        let mut wait = 0;
-        let mut retry = times;
+        let start = Instant::now();
+        let deadline = interval * times as u32;
+        let mut height = self.heads.as_ref().map(|heads| heads.height()).unwrap_or_default();
         loop {
             if let Some(receipt) = self.transaction_receipt(hash) {
-                wait = times + 1 - retry;
-                break (wait, Some(receipt));
-            } else {
-                std::thread::sleep(interval);
-                retry -= 1;
-                if retry == 0 {
-                    break (wait, None);
-                }
+                break (start.elapsed().as_secs() + 1, Some(receipt));
             }
-        }
-    }
-
-    pub fn check_wait_overflow(&self, id: usize, interval: Option<u64>) {
-        loop {
-            let flag = self.overflow_flag.load(Ordering::Relaxed);
-            if flag == 0 || flag == id {
-                break;
+            if start.elapsed() >= deadline {
+                break (0, None);
             }
-            std::thread::sleep(Duration::from_secs(interval.unwrap_or(3)));
-        }
-    }
-
-    pub fn parse_error(&self, err: Option<&dyn StdError>) -> Error {
-        match err {
-            Some(e) => {
-                let err_str = e.to_string();
-                if err_str.contains("broadcast_tx_sync") {
-                    Error::SyncTx
-                } else if err_str.contains("Transaction check error") {
-                    Error::CheckTx
-                } else if err_str.contains("error sending request") {
-                    Error::SendErr
-                } else if err_str.contains("InternalError") {
-                    if err_str.contains("InvalidNonce") {
-                        Error::TxInternalErr(InternalError::InvalidNonce(err_str))
-                    } else {
-                        Error::TxInternalErr(InternalError::Other(err_str))
-                    }
-                } else {
-                    Error::Unknown(err_str)
+            match self.heads.as_ref().filter(|heads| heads.is_alive()) {
+                Some(heads) => {
+                    height = self.rt.block_on(heads.wait_after(height, interval)).unwrap_or(height);
                 }
+                None => std::thread::sleep(interval),
             }
-            None => Error::Unknown("empty error".to_string()),
         }
     }
 
@@ -293,6 +423,8 @@ impl TestClient {
         let gas_price = self.gas_price();
         let nonce = RefCell::new(self.pending_nonce(source_address).unwrap());
         let last_err_cnt = RefCell::new(0u64);
+        self.progress
+            .start(&targets.iter().map(|(account, _)| *account).collect::<Vec<_>>());
         targets
             .iter()
             .map(|(account, am)| {
@@ -316,46 +448,37 @@ impl TestClient {
             .enumerate()
             // Sign the txs (can be done offline)
             .for_each(|(idx, (mut tx_object, mut metric))| {
+                // category of the last error, if the tx never gets accepted
+                let mut failure = "unknown";
                 match self
                     .rt
                     .block_on(self.accounts.sign_transaction(tx_object.clone(), &source_sk))
                 {
                     Ok(signed) => {
-                        self.check_wait_overflow(id, None);
-                        match self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction)) {
+                        let sent = self.rt.block_on(async {
+                            let _permit = self.concurrency.acquire().await;
+                            self.send_raw_async(signed.raw_transaction).await
+                        });
+                        match sent {
                             Ok(hash) => {
+                                self.concurrency.succeeded();
                                 metric.hash = Some(hash);
                                 debug!("{}/{} {:?} {:?}", idx + 1, total, metric.to, hash);
                                 nonce.borrow_mut().add_assign(U256::one());
-                                if let Ok(val) =
-                                    self.overflow_flag
-                                        .compare_exchange(id, 0, Ordering::Acquire, Ordering::Relaxed)
-                                {
-                                    warn!("overflow flag cleared by {} me {}", val, id);
-                                }
                             }
                             Err(e) => {
-                                match self.parse_error(e.source()) {
-                                    Error::SyncTx => {
-                                        if let Ok(mut val) = self.overflow_flag.compare_exchange(
-                                            0,
-                                            id,
-                                            Ordering::Acquire,
-                                            Ordering::Relaxed,
-                                        ) {
-                                            if val == 0 {
-                                                val = id;
-                                            }
-                                            warn!("overflow flag set by {}, me {}", val, id);
-                                        }
-                                        self.check_wait_overflow(id, None);
+                                self.concurrency.failed(&e);
+                                failure = e.category();
+                                match &e {
+                                    Error::SyncTx(_) => {
+                                        warn!("mempool is full, {} sends in flight", self.concurrency.limit());
                                     }
-                                    Error::SendErr => {
+                                    Error::SendErr(_) | Error::Timeout(_) => {
                                         // TODO: adjust timeout
                                         error!("Failed to send request, increase timeout could be helpful");
                                     }
-                                    Error::CheckTx => {
-                                        error!("Transaction check error");
+                                    Error::CheckTx(e) => {
+                                        error!("Transaction check error: {}", e);
                                     }
                                     Error::TxInternalErr(e) => {
                                         error!("Internal error: {:?}", e);
@@ -364,42 +487,37 @@ impl TestClient {
                                         error!("other error {:?}", e);
                                     }
                                 }
+                                // keep sending the tx while the node pushes back
                                 let mut skip = false;
-                                while self.overflow_flag.load(Ordering::Relaxed) == id {
+                                let mut back_pressure = e.is_back_pressure();
+                                while back_pressure {
                                     warn!("try to check if error persists {}", id);
+                                    std::thread::sleep(Duration::from_secs(3));
                                     let mut tx_object = tx_object.clone();
                                     if let Some(nonce) = self.pending_nonce(source_address) {
                                         tx_object.nonce = Some(nonce);
                                     }
-                                    if let Ok(signed) =
-                                        self.rt.block_on(self.accounts.sign_transaction(tx_object, &source_sk))
-                                    {
-                                        match self
-                                            .rt
-                                            .block_on(self.eth.send_raw_transaction(signed.raw_transaction.clone()))
-                                        {
-                                            Ok(_) => {
-                                                if self.overflow_flag.compare_exchange(
-                                                    id,
-                                                    0,
-                                                    Ordering::Acquire,
-                                                    Ordering::Relaxed,
-                                                ) != Ok(id)
-                                                {
-                                                    panic!("This should never happened");
-                                                } else {
-                                                    skip = true;
-                                                    break;
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!("Failed to send tx {:?}, continue to trying", e);
-                                            }
+                                    let resend_nonce = tx_object.nonce.unwrap_or_default();
+                                    let resent = self.rt.block_on(async {
+                                        let signed = self.sign_async(tx_object, &source_sk).await?;
+                                        let _permit = self.concurrency.acquire().await;
+                                        self.send_raw_async(signed.raw_transaction).await
+                                    });
+                                    match resent {
+                                        Ok(hash) => {
+                                            self.concurrency.succeeded();
+                                            metric.hash = Some(hash);
+                                            *nonce.borrow_mut() = resend_nonce + 1;
+                                            skip = true;
+                                            back_pressure = false;
+                                        }
+                                        Err(e) => {
+                                            self.concurrency.failed(&e);
+                                            failure = e.category();
+                                            error!("Failed to send tx {:?}, continue to trying", e);
+                                            back_pressure = e.is_back_pressure();
                                         }
-                                    } else {
-                                        error!("Failed to sign tx, nothing we can do...")
                                     }
-                                    std::thread::sleep(Duration::from_secs(3));
                                 }
                                 if need_retry && !skip {
                                     error!("retry for error {:?}", e);
@@ -415,8 +533,13 @@ impl TestClient {
                                     if let Ok(signed) =
                                         self.rt.block_on(self.accounts.sign_transaction(tx_object, &source_sk))
                                     {
-                                        match self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction)) {
+                                        let retried = self.rt.block_on(async {
+                                            let _permit = self.concurrency.acquire().await;
+                                            self.send_raw_async(signed.raw_transaction).await
+                                        });
+                                        match retried {
                                             Ok(hash) => {
+                                                self.concurrency.succeeded();
                                                 metric.hash = Some(hash);
                                                 warn!(
                                                     "retry {}/{} {:?} {:?} {}",
@@ -430,6 +553,8 @@ impl TestClient {
                                                 nonce.borrow_mut().add_assign(U256::one());
                                             }
                                             Err(e) => {
+                                                self.concurrency.failed(&e);
+                                                failure = e.category();
                                                 error!(
                                                     "give up send {}/{} {:?} {} {:?}",
                                                     idx + 1,
@@ -458,6 +583,7 @@ impl TestClient {
                         }
                     }
                     Err(e) => {
+                        failure = Error::from(e.clone()).category();
                         error!("give up sign {}/{} {:?} {:?}", idx + 1, total, metric.to, e);
                         metric.status = 98;
                         // retrieve nonce if failed to send tx
@@ -465,6 +591,10 @@ impl TestClient {
                     }
                 }
 
+                match metric.hash {
+                    Some(_) => self.progress.sent(metric.to),
+                    None => self.progress.unsent(failure),
+                }
                 results.push(metric);
             });
 
@@ -480,29 +610,23 @@ impl TestClient {
         info!("Waiting for final results...");
 
         results.iter_mut().enumerate().for_each(|(idx, metric)| {
-            let mut retry = wait_time;
-            loop {
-                if let Some(hash) = metric.hash {
-                    if let Some(receipt) = self.transaction_receipt(hash) {
-                        if let Some(status) = receipt.status {
-                            if status == U64::from(1u64) {
-                                succeed += 1;
-                                metric.status = 1;
-                            }
-                        }
-                        metric.wait = wait_time + 1 - retry;
-                        break;
-                    } else {
-                        std::thread::sleep(Duration::from_secs(1));
-                        retry -= 1;
-                        if retry == 0 {
-                            metric.wait = wait_time;
-                            break;
+            if let Some(hash) = metric.hash {
+                match self.wait_for_tx_receipt(hash, Duration::from_secs(1), wait_time) {
+                    (wait, Some(receipt)) => {
+                        if receipt.status == Some(U64::from(1u64)) {
+                            succeed += 1;
+                            metric.status = 1;
                         }
+                        self.progress.confirmed(metric.to, Some(metric.status == 1));
+                        metric.wait = wait;
+                    }
+                    (_, None) => {
+                        self.progress.confirmed(metric.to, None);
+                        metric.wait = wait_time
                     }
                 }
             }
-            println!(
+            info!(
                 "{}/{} {:?} {:?} {}",
                 idx,
                 total,
@@ -530,25 +654,274 @@ impl TestClient {
         gas_price: Option<U256>,
         nonce: Option<U256>,
     ) -> Result<H256> {
+        self.rt
+            .block_on(self.distribution_simple_async(source, target, chain_id, gas_price, nonce))
+    }
+
+    pub async fn distribution_simple_async(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        let tx_object = Self::transfer_tx(target, chain_id, gas_price, nonce);
+        self.sign_and_send(tx_object, source).await
+    }
+
+    /// Send the whole balance of `source` minus the fee to `to`, the receipt is not waited.
+    ///
+    /// The fee is `VALUE_TRANSFER_GAS * gas_price`, nothing is sent if the balance doesn't cover it.
+    pub fn sweep(
+        &self,
+        source: (secp256k1::SecretKey, Address),
+        to: Address,
+        chain_id: u64,
+        gas_price: U256,
+    ) -> SweepMetric {
+        let (source_sk, from) = source;
+        let mut metric = SweepMetric {
+            from,
+            to,
+            status: 99,
+            ..Default::default()
+        };
+        let fee = gas_price * VALUE_TRANSFER_GAS;
+        let sent = self.rt.block_on(async {
+            metric.balance = self.try_balance_async(from, None).await?;
+            if metric.balance <= fee {
+                return Ok(None);
+            }
+            metric.amount = metric.balance - fee;
+            let nonce = self
+                .pending_nonce_inner_async(from, Some(1), Some(3))
+                .await
+                .ok_or_else(|| Error::Unknown(format!("failed to get nonce of {:?}", from)))?;
+            let mut tx_object = Self::transfer_tx(&(to, metric.amount), Some(chain_id), Some(gas_price), Some(nonce));
+            tx_object.gas = U256::from(VALUE_TRANSFER_GAS);
+            self.sign_and_send(tx_object, &source_sk).await.map(Some)
+        });
+        match sent {
+            Ok(Some(hash)) => metric.hash = Some(hash),
+            Ok(None) => {
+                metric.amount = U256::zero();
+                metric.status = 0;
+            }
+            Err(e) => metric.error = Some(e.to_string()),
+        }
+        metric
+    }
+
+    /// a value transfer transaction
+    pub fn transfer_tx(
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> TransactionParameters {
         let (account, amount) = target;
-        let tx_object = TransactionParameters {
+        TransactionParameters {
             to: Some(*account),
             value: *amount,
             chain_id,
             gas_price,
             nonce,
             ..Default::default()
+        }
+    }
+
+    pub fn erc20_transfer_simple(
+        &self,
+        source: &secp256k1::SecretKey,
+        token: &Erc20,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        self.rt
+            .block_on(self.erc20_transfer_simple_async(source, token, target, chain_id, gas_price, nonce))
+    }
+
+    pub async fn erc20_transfer_simple_async(
+        &self,
+        source: &secp256k1::SecretKey,
+        token: &Erc20,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        let tx_object = Self::erc20_transfer_tx(token, target, chain_id, gas_price, nonce)?;
+        self.sign_and_send(tx_object, source).await
+    }
+
+    /// a token transfer transaction
+    pub fn erc20_transfer_tx(
+        token: &Erc20,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> Result<TransactionParameters> {
+        let (account, amount) = target;
+        Ok(TransactionParameters {
+            to: token.address,
+            data: token.transfer_data(*account, *amount)?,
+            gas: U256::from(TRANSFER_GAS),
+            chain_id,
+            gas_price,
+            nonce,
+            ..Default::default()
+        })
+    }
+
+    /// a transaction deploying the bundled test token, the whole supply is owned by the deployer
+    pub fn erc20_deploy_tx(
+        token: &Erc20,
+        supply: U256,
+        chain_id: Option<u64>,
+        gas_price: Option<U256>,
+        nonce: Option<U256>,
+    ) -> Result<TransactionParameters> {
+        Ok(TransactionParameters {
+            to: None,
+            data: token.deploy_data(supply, "Findora Test Token", 18, "FTT")?,
+            gas: U256::from(DEPLOY_GAS),
+            chain_id,
+            gas_price,
+            nonce,
+            ..Default::default()
+        })
+    }
+
+    /// deploy the bundled test token from root account, the whole supply is owned by root
+    pub fn deploy_erc20(&self, supply: U256, block_time: &Option<u64>) -> Result<Erc20> {
+        let mut token = Erc20::default();
+        let tx_object = Self::erc20_deploy_tx(
+            &token,
+            supply,
+            self.chain_id().map(|id| id.as_u64()),
+            self.gas_price(),
+            self.pending_nonce(self.root_addr),
+        )?;
+        let hash = self.rt.block_on(self.sign_and_send(tx_object, &self.root_sk))?;
+        let wait_time = block_time.unwrap_or(BLOCK_TIME) * 3 + 1;
+        match self.wait_for_tx_receipt(hash, Duration::from_secs(1), wait_time) {
+            (_, Some(receipt)) if receipt.status == Some(U64::from(1u64)) => {
+                token.address = receipt.contract_address;
+                info!("token deployed at {:?}", token.address);
+                Ok(token)
+            }
+            (_, receipt) => Err(Error::Unknown(format!(
+                "failed to deploy token {:?}: {:?}",
+                hash, receipt
+            ))),
+        }
+    }
+
+    pub fn erc20_balance(&self, token: &Erc20, owner: Address) -> Option<U256> {
+        self.rt.block_on(self.erc20_balance_async(token, owner)).ok()
+    }
+
+    /// token balance of `owner` by `eth_call`
+    pub async fn erc20_balance_async(&self, token: &Erc20, owner: Address) -> Result<U256> {
+        let req = CallRequest {
+            to: token.address,
+            data: Some(token.balance_of_data(owner)?),
+            ..Default::default()
         };
-        // Sign the txs (can be done offline)
-        match self.rt.block_on(self.accounts.sign_transaction(tx_object, source)) {
-            Ok(signed) => {
-                let result = self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction));
-                match result {
-                    Err(e) => Err(self.parse_error(e.source())),
-                    Ok(hash) => Ok(hash),
+        let data = self.eth.call(req, None).await.map_err(Error::from)?;
+        token
+            .decode_balance(&data.0)
+            .ok_or_else(|| Error::Unknown(format!("invalid balance {:?}", data)))
+    }
+
+    /// transfer tokens from root account to targets, and wait for the receipts
+    pub fn erc20_distribution(
+        &self,
+        token: &Erc20,
+        targets: &[(Address, U256)],
+        block_time: &Option<u64>,
+    ) -> Result<TransferMetrics> {
+        let total = targets.len();
+        let wait_time = block_time.unwrap_or(BLOCK_TIME) * 3 + 1;
+        let chain_id = self.chain_id().map(|id| id.as_u64());
+        let gas_price = self.gas_price();
+        let mut nonce = self
+            .pending_nonce(self.root_addr)
+            .ok_or_else(|| Error::Unknown("failed to get nonce".to_string()))?;
+        let mut results = targets
+            .iter()
+            .enumerate()
+            .map(|(idx, target)| {
+                let mut metric = TxMetric {
+                    to: target.0,
+                    amount: target.1,
+                    status: 99,
+                    ..Default::default()
+                };
+                match self.erc20_transfer_simple(&self.root_sk, token, target, chain_id, gas_price, Some(nonce)) {
+                    Ok(hash) => {
+                        debug!("{}/{} {:?} {:?}", idx + 1, total, metric.to, hash);
+                        metric.hash = Some(hash);
+                        nonce.add_assign(U256::one());
+                    }
+                    Err(e) => {
+                        error!("give up send {}/{} {:?} {:?}", idx + 1, total, metric.to, e);
+                        metric.status = 98;
+                        if e.is_invalid_nonce() {
+                            if let Some(n) = self.pending_nonce(self.root_addr) {
+                                nonce = n;
+                            }
+                        }
+                    }
+                }
+                metric
+            })
+            .collect::<Vec<_>>();
+
+        info!("Waiting for final results...");
+        let mut succeed = 0u64;
+        results.iter_mut().for_each(|metric| {
+            if let Some(hash) = metric.hash {
+                let (wait, receipt) = self.wait_for_tx_receipt(hash, Duration::from_secs(1), wait_time);
+                metric.wait = wait;
+                if receipt.and_then(|r| r.status) == Some(U64::from(1u64)) {
+                    succeed += 1;
+                    metric.status = 1;
                 }
             }
-            Err(e) => Err(self.parse_error(e.source())),
-        }
+        });
+        info!("Token transfer succeeded: {}/{}", succeed, total);
+
+        Ok(TransferMetrics {
+            from: self.root_addr,
+            total: total as u64,
+            succeed,
+            txs: results,
+        })
+    }
+
+    async fn sign_and_send(&self, tx_object: TransactionParameters, source: &secp256k1::SecretKey) -> Result<H256> {
+        // Sign the txs (can be done offline)
+        let signed = self.sign_async(tx_object, source).await?;
+        self.send_raw_async(signed.raw_transaction).await
+    }
+
+    pub async fn sign_async(
+        &self,
+        tx_object: TransactionParameters,
+        source: &secp256k1::SecretKey,
+    ) -> Result<SignedTransaction> {
+        self.accounts
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(Error::from)
+    }
+
+    pub async fn send_raw_async(&self, raw: Bytes) -> Result<H256> {
+        self.eth.send_raw_transaction(raw).await.map_err(Error::from)
     }
 }
@@ -0,0 +1,1066 @@
+use crate::guard::Guard;
+use feth::{
+    contract::Erc20,
+    corpus::{Corpus, SignedTx},
+    error::{Error, Result},
+    latency::{LatencySummary, LatencyTracker, MAX_LATENCY},
+    nonce::NonceManager,
+    report::ErrorSample,
+    TestClient, TransferMetrics, TxFee, TxMetric,
+};
+use futures::StreamExt;
+use hdrhistogram::Histogram;
+use log::info;
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{sync::mpsc, time::Instant};
+use web3::types::{Address, H256, U256, U64};
+
+/// A source key with the targets it will send to, one target per transaction
+pub(crate) type Source = (secp256k1::SecretKey, Address, Vec<(Address, U256)>);
+
+/// token supply of a contract deployed by a test
+const DEPLOY_SUPPLY: u64 = 1_000_000;
+/// distinct error messages kept by a sender
+const MAX_ERROR_SAMPLES: usize = 100;
+/// receipts fetched at the same time by `transfer_metrics`
+const RECEIPT_FETCHES: usize = 32;
+
+/// Kind of a request in a workload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    /// value transfer
+    Transfer,
+    /// token transfer
+    Token,
+    /// token contract deployment
+    Deploy,
+    /// token balance by `eth_call`
+    Call,
+    /// `eth_getBalance`
+    Balance,
+}
+
+impl Kind {
+    /// a read request, it sends no tx
+    pub fn is_read(&self) -> bool {
+        matches!(self, Kind::Call | Kind::Balance)
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Kind::Transfer => "transfer",
+            Kind::Token => "token",
+            Kind::Deploy => "deploy",
+            Kind::Call => "call",
+            Kind::Balance => "balance",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "transfer" => Ok(Kind::Transfer),
+            "token" => Ok(Kind::Token),
+            "deploy" => Ok(Kind::Deploy),
+            "call" => Ok(Kind::Call),
+            "balance" => Ok(Kind::Balance),
+            _ => Err(format!(
+                "Invalid kind {}: transfer, token, deploy, call and balance are supported",
+                s
+            )),
+        }
+    }
+}
+
+/// A weighted mix of request kinds
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mix<K = Kind> {
+    pub kinds: Vec<(K, u32)>,
+}
+
+impl Mix<Kind> {
+    /// if the test token is used
+    pub fn needs_token(&self) -> bool {
+        self.kinds.iter().any(|(k, _)| matches!(k, Kind::Token | Kind::Call))
+    }
+}
+
+impl<K: Copy> Mix<K> {
+    pub fn single(kind: K) -> Self {
+        Self { kinds: vec![(kind, 1)] }
+    }
+
+    /// the next kind by smooth weighted round-robin, `current` keeps the state
+    pub fn pick(&self, current: &mut Vec<i64>) -> K {
+        current.resize(self.kinds.len(), 0);
+        let total = self.kinds.iter().map(|(_, w)| *w as i64).sum::<i64>();
+        self.kinds
+            .iter()
+            .zip(current.iter_mut())
+            .for_each(|((_, w), c)| *c += *w as i64);
+        let (idx, _) = current
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, c)| (**c, -(*i as i64)))
+            .unwrap();
+        current[idx] -= total;
+        self.kinds[idx].0
+    }
+}
+
+impl<K: FromStr<Err = String>> FromStr for Mix<K> {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // transfer=60,token=20,deploy=5,call=10,balance=5
+        let kinds = s
+            .split(',')
+            .map(|seg| {
+                let (kind, weight) = seg
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid mix {}: kind=weight expected", seg))?;
+                let weight = weight
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid weight: {}", weight))?;
+                Ok((kind.trim().parse::<K>()?, weight))
+            })
+            .filter(|kw| !matches!(kw, Ok((_, 0))))
+            .collect::<std::result::Result<Vec<_>, String>>()?;
+        if kinds.is_empty() {
+            return Err("Empty mix".to_owned());
+        }
+        Ok(Self { kinds })
+    }
+}
+
+/// Requests of one kind
+#[derive(Debug, Clone)]
+pub(crate) struct KindStats {
+    pub total: u64,
+    pub succeed: u64,
+    /// response time, milliseconds
+    pub latency: Histogram<u64>,
+}
+
+impl Default for KindStats {
+    fn default() -> Self {
+        Self {
+            total: 0,
+            succeed: 0,
+            latency: Histogram::new_with_bounds(1, MAX_LATENCY, 3).unwrap(),
+        }
+    }
+}
+
+impl KindStats {
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary::from(&self.latency)
+    }
+}
+
+/// Everything needed to build and send a test transaction through one endpoint
+pub(crate) struct Sender {
+    pub url: String,
+    pub client: Arc<TestClient>,
+    pub token: Option<Erc20>,
+    /// the contract to deploy
+    deployer: Erc20,
+    pub chain_id: u64,
+    pub fee: TxFee,
+    pub latency: Arc<LatencyTracker>,
+    pub nonces: Arc<NonceManager>,
+    pub succeed: AtomicU64,
+    pub failed: AtomicU64,
+    /// every sent tx with its source address
+    records: Mutex<Vec<(Address, TxMetric)>>,
+    keep_records: bool,
+    /// failed sends by error category
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// failed sends by error message
+    error_samples: Mutex<BTreeMap<String, ErrorSample>>,
+    /// sends by source key, all and succeeded ones
+    keys: Mutex<BTreeMap<Address, (u64, u64)>>,
+    mix: Mix,
+    /// state of picking kinds from `mix`
+    picker: Mutex<Vec<i64>>,
+    kinds: Mutex<BTreeMap<Kind, KindStats>>,
+    /// stops the test once a threshold is crossed
+    pub guard: Arc<Guard>,
+}
+
+impl Sender {
+    pub fn new(
+        url: String,
+        client: TestClient,
+        token: Option<Erc20>,
+        chain_id: u64,
+        fee: TxFee,
+        latency: Arc<LatencyTracker>,
+        nonces: Arc<NonceManager>,
+    ) -> Self {
+        Self {
+            url,
+            client: Arc::new(client),
+            token,
+            deployer: Erc20::default(),
+            chain_id,
+            fee,
+            latency,
+            nonces,
+            succeed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            records: Mutex::new(vec![]),
+            keep_records: true,
+            errors: Mutex::new(BTreeMap::new()),
+            error_samples: Mutex::new(BTreeMap::new()),
+            keys: Mutex::new(BTreeMap::new()),
+            mix: Mix::single(Kind::Transfer),
+            picker: Mutex::new(vec![]),
+            kinds: Mutex::new(BTreeMap::new()),
+            guard: Arc::new(Guard::default()),
+        }
+    }
+
+    /// share the abort thresholds of a test
+    pub fn with_guard(mut self, guard: Arc<Guard>) -> Self {
+        self.guard = guard;
+        self
+    }
+
+    /// send requests of the kinds in `mix`, value transfers by default
+    pub fn with_mix(mut self, mix: Mix) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    /// don't keep a record of every tx, for long running tests
+    pub fn without_records(mut self) -> Self {
+        self.keep_records = false;
+        self
+    }
+
+    /// send one request of the next kind in the mix from `source` to `target`, returns the kind and
+    /// if it succeeded
+    pub async fn send(
+        &self,
+        source: &secp256k1::SecretKey,
+        address: Address,
+        target: &(Address, U256),
+    ) -> (Kind, bool) {
+        let kind = self.mix.pick(&mut self.picker.lock().unwrap());
+        let start = std::time::Instant::now();
+        let ok = match kind {
+            Kind::Balance => self.read(self.client.try_balance_async(target.0, None).await),
+            Kind::Call => match &self.token {
+                Some(token) => self.read(self.client.erc20_balance_async(token, target.0).await),
+                None => false,
+            },
+            _ => matches!(self.send_tx(kind, source, address, target).await, Some(Ok(_))),
+        };
+        let mut kinds = self.kinds.lock().unwrap();
+        let stats = kinds.entry(kind).or_default();
+        stats.total += 1;
+        if ok {
+            stats.succeed += 1;
+            stats.latency.saturating_record(start.elapsed().as_millis() as u64);
+        }
+        (kind, ok)
+    }
+
+    /// failed sends by error category
+    pub fn error_counts(&self) -> BTreeMap<&'static str, u64> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    /// report the result of a read to the concurrency, returns if it succeeded
+    fn read<T>(&self, result: Result<T>) -> bool {
+        match result {
+            Ok(_) => {
+                self.client.concurrency.succeeded();
+                true
+            }
+            Err(e) => {
+                self.client.concurrency.failed(&e);
+                false
+            }
+        }
+    }
+
+    async fn send_tx(
+        &self,
+        kind: Kind,
+        source: &secp256k1::SecretKey,
+        address: Address,
+        target: &(Address, U256),
+    ) -> Option<Result<H256>> {
+        let nonce = self.nonces.next(&self.client, address).await;
+        let result = match nonce {
+            Some(nonce) => Some(self.sign_and_send(kind, source, target, nonce).await),
+            None => None,
+        };
+        if let (Some(nonce), Some(Err(e))) = (nonce, &result) {
+            if e.is_invalid_nonce() {
+                self.nonces.resync(address);
+            } else {
+                self.nonces.release(address, nonce);
+            }
+        }
+        match kind {
+            Kind::Deploy => self.record(address, &(Address::zero(), U256::zero()), result.as_ref()),
+            _ => self.record(address, target, result.as_ref()),
+        }
+        result
+    }
+
+    /// send a pre-signed transaction
+    pub async fn send_signed(&self, tx: &SignedTx) -> Result<H256> {
+        let start = std::time::Instant::now();
+        let result = self.client.send_raw_async(tx.raw.clone()).await;
+        if let Ok(hash) = result {
+            self.latency.acked(hash, start);
+        }
+        self.record(tx.from, &(tx.to, tx.amount), Some(&result));
+        result
+    }
+
+    /// count the result of a send, `None` if no nonce was available
+    fn record(&self, address: Address, target: &(Address, U256), result: Option<&Result<H256>>) {
+        let mut metric = TxMetric {
+            to: target.0,
+            amount: target.1,
+            status: 99,
+            ..Default::default()
+        };
+        self.guard.record(result);
+        {
+            let mut keys = self.keys.lock().unwrap();
+            let sends = keys.entry(address).or_default();
+            sends.0 += 1;
+            sends.1 += matches!(result, Some(Ok(_))) as u64;
+        }
+        match result {
+            Some(Ok(hash)) => {
+                self.client.concurrency.succeeded();
+                self.succeed.fetch_add(1, Relaxed);
+                metric.hash = Some(*hash);
+            }
+            failed => {
+                if let Some(Err(e)) = failed {
+                    self.client.concurrency.failed(e);
+                }
+                self.failed.fetch_add(1, Relaxed);
+                let (category, message) = match failed {
+                    Some(Err(e)) => (e.category(), e.to_string()),
+                    _ => ("nonce", "no nonce available".to_string()),
+                };
+                *self.errors.lock().unwrap().entry(category).or_default() += 1;
+                let mut samples = self.error_samples.lock().unwrap();
+                if let Some(sample) = samples.get_mut(&message) {
+                    sample.count += 1;
+                } else if samples.len() < MAX_ERROR_SAMPLES {
+                    let sample = ErrorSample {
+                        category: category.to_string(),
+                        message: message.clone(),
+                        count: 1,
+                    };
+                    samples.insert(message, sample);
+                }
+            }
+        }
+        if self.keep_records {
+            self.records.lock().unwrap().push((address, metric));
+        }
+    }
+
+    async fn sign_and_send(
+        &self,
+        kind: Kind,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        nonce: U256,
+    ) -> Result<H256> {
+        let (chain_id, nonce) = (Some(self.chain_id), Some(nonce));
+        let mut tx_object = match (kind, &self.token) {
+            (Kind::Transfer, _) => TestClient::transfer_tx(target, chain_id, None, nonce),
+            (Kind::Token, Some(token)) => TestClient::erc20_transfer_tx(token, target, chain_id, None, nonce)?,
+            (Kind::Deploy, _) => {
+                let supply = U256::exp10(18) * DEPLOY_SUPPLY;
+                TestClient::erc20_deploy_tx(&self.deployer, supply, chain_id, None, nonce)?
+            }
+            _ => return Err(Error::NotSupport(format!("{} transaction", kind))),
+        };
+        self.fee.apply(&mut tx_object);
+        let start = std::time::Instant::now();
+        let signed = self.client.sign_async(tx_object, source).await?;
+        self.latency.signed(start.elapsed());
+        let hash = self.client.send_raw_async(signed.raw_transaction).await?;
+        self.latency.acked(hash, start);
+        Ok(hash)
+    }
+}
+
+/// succeeded, failed counts and TPS of every endpoint
+pub(crate) fn endpoint_summary(senders: &[Sender], elapsed_secs: f64) -> Vec<String> {
+    senders
+        .iter()
+        .map(|sender| {
+            let succeed = sender.succeed.load(Relaxed);
+            let failed = sender.failed.load(Relaxed);
+            format!(
+                "Endpoint summary: {},total,{}/{},errors,{},TPS,{:.3}",
+                sender.url,
+                succeed,
+                succeed + failed,
+                failed,
+                succeed as f64 / elapsed_secs
+            )
+        })
+        .collect()
+}
+
+/// requests of all endpoints by kind
+pub(crate) fn kind_stats(senders: &[Sender]) -> BTreeMap<Kind, KindStats> {
+    let mut kinds: BTreeMap<Kind, KindStats> = BTreeMap::new();
+    senders.iter().for_each(|sender| {
+        sender.kinds.lock().unwrap().iter().for_each(|(kind, stats)| {
+            let all = kinds.entry(*kind).or_default();
+            all.total += stats.total;
+            all.succeed += stats.succeed;
+            all.latency.add(&stats.latency).unwrap();
+        })
+    });
+    kinds
+}
+
+/// failed sends of all endpoints by error category
+pub(crate) fn error_counts(senders: &[Sender]) -> BTreeMap<String, u64> {
+    let mut errors = BTreeMap::new();
+    senders.iter().for_each(|sender| {
+        sender.errors.lock().unwrap().iter().for_each(|(category, count)| {
+            *errors.entry(category.to_string()).or_default() += count;
+        })
+    });
+    errors
+}
+
+/// sends of every source key, all and succeeded ones
+pub(crate) fn key_progress(senders: &[Sender]) -> BTreeMap<Address, (u64, u64)> {
+    let mut keys: BTreeMap<Address, (u64, u64)> = BTreeMap::new();
+    senders.iter().for_each(|sender| {
+        sender
+            .keys
+            .lock()
+            .unwrap()
+            .iter()
+            .for_each(|(address, (sent, succeed))| {
+                let sends = keys.entry(*address).or_default();
+                sends.0 += sent;
+                sends.1 += succeed;
+            })
+    });
+    keys
+}
+
+/// distinct error messages of all endpoints, the most frequent first
+pub(crate) fn error_samples(senders: &[Sender]) -> Vec<ErrorSample> {
+    let mut samples: BTreeMap<String, ErrorSample> = BTreeMap::new();
+    senders.iter().for_each(|sender| {
+        sender.error_samples.lock().unwrap().values().for_each(|sample| {
+            samples
+                .entry(sample.message.clone())
+                .and_modify(|s| s.count += sample.count)
+                .or_insert_with(|| sample.clone());
+        })
+    });
+    let mut samples = samples.into_values().collect::<Vec<_>>();
+    samples.sort_by_key(|s| std::cmp::Reverse(s.count));
+    samples
+}
+
+/// error counts by category as `category,count,...`, only the ones increased since `last`
+pub(crate) fn error_delta(errors: &BTreeMap<String, u64>, last: &BTreeMap<String, u64>) -> String {
+    let delta = errors
+        .iter()
+        .filter_map(|(category, count)| {
+            let delta = count - last.get(category).copied().unwrap_or_default();
+            (delta > 0).then(|| format!("{},{}", category, delta))
+        })
+        .collect::<Vec<_>>();
+    if delta.is_empty() {
+        "none".to_string()
+    } else {
+        delta.join(",")
+    }
+}
+
+/// metrics of all the sent txs grouped by source address, a tx succeeded if its receipt says so
+pub(crate) fn transfer_metrics(senders: &[Sender], latency: &LatencyTracker) -> Vec<TransferMetrics> {
+    let mut metrics: BTreeMap<Address, TransferMetrics> = BTreeMap::new();
+    senders.iter().for_each(|sender| {
+        let records = sender.records.lock().unwrap();
+        let included = records
+            .iter()
+            .filter_map(|(_, m)| m.hash)
+            .filter(|h| latency.inclusion_of(h).is_some());
+        let statuses = receipt_statuses(sender, included);
+        records.iter().for_each(|(from, metric)| {
+            let mut metric = metric.clone();
+            let m = metrics.entry(*from).or_insert_with(|| TransferMetrics {
+                from: *from,
+                ..Default::default()
+            });
+            if let Some(wait) = metric.hash.and_then(|h| latency.inclusion_of(&h)) {
+                metric.wait = wait.as_secs();
+                if metric.hash.and_then(|h| statuses.get(&h).copied()) == Some(true) {
+                    metric.status = 1;
+                    m.succeed += 1;
+                }
+            }
+            m.total += 1;
+            m.txs.push(metric);
+        })
+    });
+    metrics.into_values().collect()
+}
+
+/// whether the receipt of each of `hashes` says the tx succeeded, txs without a receipt are left out
+fn receipt_statuses(sender: &Sender, hashes: impl Iterator<Item = H256>) -> BTreeMap<H256, bool> {
+    let client = &sender.client;
+    let fetches = futures::stream::iter(hashes).map(|hash| async move {
+        client
+            .transaction_receipt_async(hash)
+            .await
+            .map(|receipt| (hash, receipt.status == Some(U64::one())))
+    });
+    client.runtime().block_on(
+        fetches
+            .buffer_unordered(RECEIPT_FETCHES)
+            .filter_map(|s| async move { s })
+            .collect(),
+    )
+}
+
+/// Shape of the send rate(tps) within a stage
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Shape {
+    /// constant rate
+    Step(u64),
+    /// linear ramp from the first rate to the second one
+    Ramp(u64, u64),
+    /// base rate, with a burst at peak rate in the middle fifth of the stage
+    Spike(u64, u64),
+    /// one sine period between the low and the high rate
+    Sine(u64, u64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stage {
+    pub shape: Shape,
+    /// seconds
+    pub duration: u64,
+}
+
+impl Stage {
+    /// send rate at `t` seconds since the beginning of this stage
+    pub fn rate(&self, t: f64) -> f64 {
+        let progress = t / self.duration as f64;
+        match self.shape {
+            Shape::Step(rate) => rate as f64,
+            Shape::Ramp(from, to) => from as f64 + (to as f64 - from as f64) * progress,
+            Shape::Spike(base, peak) => {
+                if (0.4..0.6).contains(&progress) {
+                    peak as f64
+                } else {
+                    base as f64
+                }
+            }
+            Shape::Sine(low, high) => {
+                let (low, high) = (low as f64, high as f64);
+                (low + high) / 2.0 + (high - low) / 2.0 * (progress * 2.0 * std::f64::consts::PI).sin()
+            }
+        }
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.shape {
+            Shape::Step(rate) => write!(f, "step:{}:{}", self.duration, rate),
+            Shape::Ramp(from, to) => write!(f, "ramp:{}:{}-{}", self.duration, from, to),
+            Shape::Spike(base, peak) => write!(f, "spike:{}:{}-{}", self.duration, base, peak),
+            Shape::Sine(low, high) => write!(f, "sine:{}:{}-{}", self.duration, low, high),
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // kind:duration:rate[-rate]
+        let segs: Vec<&str> = s.trim().splitn(3, ':').collect();
+        if segs.len() != 3 {
+            return Err(format!("Invalid stage {}: kind:duration:rate[-rate] expected", s));
+        }
+        let duration = segs[1]
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid stage duration: {}", segs[1]))?;
+        if duration == 0 {
+            return Err(format!("Invalid stage duration: {}", segs[1]));
+        }
+        let rates = segs[2]
+            .split('-')
+            .map(|r| r.parse::<u64>().map_err(|_| format!("Invalid stage rate: {}", segs[2])))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let shape = match (segs[0].to_lowercase().as_str(), rates.as_slice()) {
+            ("step", [rate]) => Shape::Step(*rate),
+            ("ramp", [from, to]) => Shape::Ramp(*from, *to),
+            ("spike", [base, peak]) => Shape::Spike(*base, *peak),
+            ("sine", [low, high]) if low <= high => Shape::Sine(*low, *high),
+            _ => return Err(format!("Invalid stage {}: step, ramp, spike and sine are supported", s)),
+        };
+        Ok(Self { shape, duration })
+    }
+}
+
+/// A load profile, stages run one after another
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Profile {
+    pub stages: Vec<Stage>,
+}
+
+impl Profile {
+    /// a single stage which sends `total` transactions at `rate`
+    pub fn constant(rate: u64, total: u64) -> Self {
+        Self {
+            stages: vec![Stage {
+                shape: Shape::Step(rate),
+                duration: total / rate + 1,
+            }],
+        }
+    }
+
+    /// a single stage which sends at `rate` for `secs` seconds
+    pub fn steady(rate: u64, secs: u64) -> Self {
+        Self {
+            stages: vec![Stage {
+                shape: Shape::Step(rate),
+                duration: secs,
+            }],
+        }
+    }
+
+    /// the stages repeated until they cover `secs` seconds
+    pub fn repeat(&self, secs: u64) -> Self {
+        let times = secs.div_ceil(self.duration()).max(1) as usize;
+        Self {
+            stages: (0..times).flat_map(|_| self.stages.iter().cloned()).collect(),
+        }
+    }
+
+    pub fn duration(&self) -> u64 {
+        self.stages.iter().map(|s| s.duration).sum()
+    }
+
+    /// stage index and send rate at `t` seconds since the beginning
+    pub fn at(&self, t: f64) -> Option<(usize, f64)> {
+        let mut begin = 0f64;
+        for (idx, stage) in self.stages.iter().enumerate() {
+            let end = begin + stage.duration as f64;
+            if t < end {
+                return Some((idx, stage.rate(t - begin)));
+            }
+            begin = end;
+        }
+        None
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        // --profile ramp:60:100-1000,step:120:1000,spike:30:1000-3000,sine:300:500-1500
+        let stages = s
+            .split(',')
+            .filter(|seg| !seg.trim().is_empty())
+            .map(Stage::from_str)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if stages.is_empty() {
+            return Err("Please provide one stage at least".to_owned());
+        }
+        Ok(Self { stages })
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct StageStats {
+    pub stage: String,
+    /// txs sent
+    pub total: u64,
+    pub succeed: u64,
+    /// read requests, they're not counted as txs
+    pub reads: u64,
+    pub late: u64,
+    /// planned seconds of the stage
+    pub secs: u64,
+    /// new errors by category while the stage was sent, in the format of `error_delta`
+    pub errors: String,
+}
+
+impl StageStats {
+    /// scheduled requests per second, reads included
+    pub fn offered_rate(&self) -> f64 {
+        (self.total + self.reads) as f64 / self.secs as f64
+    }
+
+    pub fn achieved_rate(&self) -> f64 {
+        self.succeed as f64 / self.secs as f64
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct OpenLoopStats {
+    /// txs sent
+    pub total: u64,
+    pub succeed: u64,
+    /// read requests, they're not counted as txs
+    pub reads: u64,
+    /// sends started more than one interval after their scheduled time
+    pub late: u64,
+    /// seconds spent on scheduling all the sends
+    pub offered_secs: f64,
+    /// seconds until the last response came back
+    pub elapsed_secs: f64,
+    pub stages: Vec<StageStats>,
+}
+
+impl OpenLoopStats {
+    /// scheduled requests per second, reads included
+    pub fn offered_rate(&self) -> f64 {
+        (self.total + self.reads) as f64 / self.offered_secs
+    }
+
+    pub fn achieved_rate(&self) -> f64 {
+        self.succeed as f64 / self.elapsed_secs
+    }
+}
+
+/// rates below this are treated as idle
+const MIN_RATE: f64 = 0.01;
+/// seconds to advance the clock while idle
+const IDLE_STEP: f64 = 0.1;
+
+/// Send transactions of `sources` following `profile`, without waiting for responses.
+///
+/// Sends are scheduled on a fixed clock and spawned as tasks, the in-flight ones are limited by
+/// the concurrency of every endpoint. The i-th send goes from source `i % sources.len()` to one of its
+/// targets in turn. Source keys are assigned to `senders` round-robin. Scheduling stops at the
+/// end of the profile, after `limit` sends, or once the guard of the senders trips. Errors are
+/// counted to the stage being scheduled when they come back.
+pub(crate) async fn open_loop(
+    senders: Arc<Vec<Sender>>,
+    sources: Arc<Vec<Source>>,
+    profile: &Profile,
+    limit: Option<u64>,
+) -> OpenLoopStats {
+    let keys = sources.len();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut stages = profile
+        .stages
+        .iter()
+        .map(|s| StageStats {
+            stage: s.to_string(),
+            secs: s.duration,
+            errors: "none".to_string(),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut next = 0f64;
+    let mut total = 0u64;
+    let mut last_log = 0u64;
+    let mut current = 0usize;
+    let mut last_errors = error_counts(&senders);
+    let guard = &senders.first().unwrap().guard;
+    while let Some((stage, rate)) = profile.at(next) {
+        if limit == Some(total) || guard.is_tripped() {
+            break;
+        }
+        if stage != current {
+            let errors = error_counts(&senders);
+            stages[current].errors = error_delta(&errors, &last_errors);
+            info!("stage {} errors {}", current + 1, stages[current].errors);
+            last_errors = errors;
+            current = stage;
+        }
+        if rate < MIN_RATE {
+            next += IDLE_STEP;
+            continue;
+        }
+        let interval = Duration::from_secs_f64(1.0 / rate);
+        let scheduled = start + Duration::from_secs_f64(next);
+        tokio::time::sleep_until(scheduled).await;
+        let i = total as usize;
+        let tx = tx.clone();
+        let senders = senders.clone();
+        let sources = sources.clone();
+        tokio::spawn(async move {
+            let (source, address, targets) = sources.get(i % keys).unwrap();
+            let target = targets.get(i / keys % targets.len()).unwrap();
+            let sender = senders.get(i % keys % senders.len()).unwrap();
+            let _permit = sender.client.concurrency.acquire().await;
+            let late = Instant::now().duration_since(scheduled) > interval;
+            let (kind, ok) = sender.send(source, *address, target).await;
+            tx.send((stage, kind.is_read(), ok, late)).unwrap();
+        });
+        total += 1;
+        next += interval.as_secs_f64();
+        if next as u64 > last_log {
+            last_log = next as u64;
+            info!(
+                "scheduled {} stage {} rate {:.3} time {}",
+                total,
+                stage + 1,
+                rate,
+                last_log
+            );
+        }
+    }
+    let offered_secs = start.elapsed().as_secs_f64();
+
+    let mut stats = OpenLoopStats {
+        offered_secs,
+        ..Default::default()
+    };
+    for _ in 0..total {
+        let (stage, read, ok, late) = rx.recv().await.unwrap();
+        if read {
+            stats.reads += 1;
+            stages[stage].reads += 1;
+        } else {
+            stats.total += 1;
+            stages[stage].total += 1;
+            if ok {
+                stats.succeed += 1;
+                stages[stage].succeed += 1;
+            }
+        }
+        if late {
+            stats.late += 1;
+            stages[stage].late += 1;
+        }
+    }
+    // the responses of the last stage are all back now
+    stages[current].errors = error_delta(&error_counts(&senders), &last_errors);
+    info!("stage {} errors {}", current + 1, stages[current].errors);
+    stats.elapsed_secs = start.elapsed().as_secs_f64();
+    stats.stages = stages;
+    stats
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RoundStats {
+    /// txs sent
+    pub total: u64,
+    pub succeed: u64,
+    /// read requests, they're not counted as txs
+    pub reads: u64,
+    pub elapsed_secs: f64,
+}
+
+/// When to stop sending rounds
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RunLimit {
+    /// transactions sent by a source key
+    Count(u64),
+    /// keep sending until then, targets of source keys are reused
+    Duration(Duration),
+}
+
+/// Send `pipeline` transactions per source key in every round, a round starts when a new block arrives.
+///
+/// The transactions of a source key in a round are sent one after another by a task, the in-flight
+/// ones are limited by the concurrency of every endpoint. Rounds stop once the guard of the senders trips.
+pub(crate) async fn round_loop(
+    senders: Arc<Vec<Sender>>,
+    sources: Arc<Vec<Source>>,
+    limit: RunLimit,
+    pipeline: u64,
+    delay: u64,
+    start_height: U64,
+) -> RoundStats {
+    let client = &senders.first().unwrap().client;
+    let guard = &senders.first().unwrap().guard;
+    let succeed = Arc::new(AtomicU64::new(0));
+    let mut last_height = start_height;
+
+    let rounds = match limit {
+        RunLimit::Count(count) => count.div_ceil(pipeline),
+        RunLimit::Duration(_) => u64::MAX,
+    };
+    let (mut total, mut reads) = (0u64, 0u64);
+    let mut last_errors = error_counts(&senders);
+    let start = Instant::now();
+    'rounds: for r in 0..rounds {
+        if matches!(limit, RunLimit::Duration(d) if start.elapsed() >= d) {
+            break;
+        }
+        last_height = loop {
+            if guard.is_tripped() {
+                break 'rounds;
+            }
+            if let Some(current) = client.wait_block_async(last_height, Duration::from_secs(1)).await {
+                break current;
+            }
+        };
+        let now = Instant::now();
+        let handles = (0..sources.len())
+            .map(|idx| {
+                let senders = senders.clone();
+                let sources = sources.clone();
+                let succeed = succeed.clone();
+                tokio::spawn(async move {
+                    let (source, address, targets) = sources.get(idx).unwrap();
+                    let sender = senders.get(idx % senders.len()).unwrap();
+                    let (mut sent, mut reads) = (0u64, 0u64);
+                    for i in r * pipeline..(r + 1) * pipeline {
+                        if sender.guard.is_tripped() {
+                            break;
+                        }
+                        let target = match limit {
+                            RunLimit::Count(count) if i >= count => break,
+                            RunLimit::Count(_) => targets.get(i as usize).unwrap(),
+                            RunLimit::Duration(_) => targets.get(i as usize % targets.len()).unwrap(),
+                        };
+                        let _permit = sender.client.concurrency.acquire().await;
+                        match sender.send(source, *address, target).await {
+                            (kind, _) if kind.is_read() => reads += 1,
+                            (_, ok) => {
+                                succeed.fetch_add(ok as u64, Relaxed);
+                                sent += 1;
+                            }
+                        }
+                    }
+                    (sent, reads)
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let (sent, read) = handle.await.unwrap();
+            total += sent;
+            reads += read;
+        }
+        let errors = error_counts(&senders);
+        let round_errors = error_delta(&errors, &last_errors);
+        last_errors = errors;
+        match limit {
+            RunLimit::Count(_) => info!(
+                "round {}/{} time {} errors {}",
+                r + 1,
+                rounds,
+                now.elapsed().as_secs(),
+                round_errors
+            ),
+            RunLimit::Duration(_) => info!(
+                "round {} time {} errors {}",
+                r + 1,
+                now.elapsed().as_secs(),
+                round_errors
+            ),
+        }
+        tokio::time::sleep(Duration::from_secs(delay)).await;
+    }
+
+    RoundStats {
+        total,
+        succeed: succeed.load(Relaxed),
+        reads,
+        elapsed_secs: start.elapsed().as_secs_f64(),
+    }
+}
+
+/// Log an aggregated summary of all endpoints every `interval`, until aborted
+pub(crate) async fn report_progress(senders: Arc<Vec<Sender>>, latency: Arc<LatencyTracker>, interval: Duration) {
+    let start = Instant::now();
+    let mut last_succeed = 0u64;
+    loop {
+        tokio::time::sleep(interval).await;
+        let succeed = senders.iter().map(|s| s.succeed.load(Relaxed)).sum::<u64>();
+        let failed = senders.iter().map(|s| s.failed.load(Relaxed)).sum::<u64>();
+        let elapsed = start.elapsed().as_secs_f64();
+        let inclusion = latency
+            .summary()
+            .into_iter()
+            .find(|(name, _)| *name == "inclusion")
+            .map(|(_, l)| l)
+            .unwrap_or_default();
+        info!(
+            "Soak summary: seconds,{:.0},total,{}/{},errors,{},TPS,{:.3},interval TPS,{:.3},pending,{},concurrency,{},inclusion p50,{},p99,{}",
+            elapsed,
+            succeed,
+            succeed + failed,
+            failed,
+            succeed as f64 / elapsed,
+            (succeed - last_succeed) as f64 / interval.as_secs_f64(),
+            latency.pending(),
+            senders.iter().map(|s| s.client.concurrency.limit()).sum::<usize>(),
+            inclusion.p50,
+            inclusion.p99,
+        );
+        last_succeed = succeed;
+    }
+}
+
+/// Send the pre-signed transactions of `corpus`, the ones of a source account one after another in nonce order.
+///
+/// Source accounts are assigned to `senders` round-robin, the in-flight sends are limited by the
+/// concurrency of every endpoint.
+pub(crate) async fn replay(senders: Arc<Vec<Sender>>, corpus: Arc<Corpus>) -> RoundStats {
+    let succeed = Arc::new(AtomicU64::new(0));
+
+    let start = Instant::now();
+    let handles = (0..corpus.sources.len())
+        .map(|idx| {
+            let senders = senders.clone();
+            let corpus = corpus.clone();
+            let succeed = succeed.clone();
+            tokio::spawn(async move {
+                let sender = senders.get(idx % senders.len()).unwrap();
+                for tx in corpus.sources.get(idx).unwrap() {
+                    let _permit = sender.client.concurrency.acquire().await;
+                    if sender.send_signed(tx).await.is_ok() {
+                        succeed.fetch_add(1, Relaxed);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    RoundStats {
+        total: corpus.len() as u64,
+        succeed: succeed.load(Relaxed),
+        reads: 0,
+        elapsed_secs: start.elapsed().as_secs_f64(),
+    }
+}
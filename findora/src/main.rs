@@ -1,23 +1,46 @@
+mod bench;
+mod cluster;
 mod commands;
+mod dashboard;
 mod db;
+mod guard;
+mod load;
+mod metrics;
 mod profiler;
 
 use std::{
     cell::RefCell,
     cmp::Ordering,
     ops::{Mul, MulAssign, Sub},
+    path::Path,
     str::FromStr,
-    sync::{
-        atomic::{AtomicU64, Ordering::Relaxed},
-        mpsc, Arc,
-    },
-    time::Duration,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
 };
 
+use bench::bench_rpc;
+use cluster::Job;
 use commands::*;
-use feth::{one_eth_key, utils::*, KeyPair, TestClient};
-use log::{debug, error, info};
+use dashboard::{Dashboard, Snapshot, LOG_FILE};
+use feth::{
+    contract::Erc20,
+    corpus::{Corpus, SignedTx},
+    heads::NewHeads,
+    keys::{load_keys, KeyFile, KeySet},
+    latency::LatencyTracker,
+    nonce::NonceManager,
+    one_eth_key, random_address,
+    report::{KindResult, RunDir, RunResult, ERRORS_FILE, METRICS_FILE, NETWORK_FILE, RESULT_FILE},
+    utils::*,
+    KeyPair, NetworkInfo, SweepMetric, TestClient, TransferMetrics, TxFee, BLOCK_TIME,
+};
+use load::{
+    endpoint_summary, error_counts, error_samples, kind_stats, open_loop, replay, report_progress, round_loop,
+    transfer_metrics, Kind, Mix, Profile, RunLimit, Sender, Source,
+};
+use log::{debug, error, info, warn};
 use rayon::prelude::*;
+use serde::Serialize;
 use web3::types::{Address, Block, BlockId, BlockNumber, TransactionId, H256, U256, U64};
 
 fn eth_transaction(network: &str, timeout: Option<u64>, hash: H256) {
@@ -159,50 +182,58 @@ fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: Op
 }
 
 fn fund_accounts(
-    network: &str,
-    timeout: Option<u64>,
+    client: &TestClient,
     block_time: u64,
     count: u64,
     am: u64,
     load: bool,
     redeposit: bool,
-) {
+    seed: Option<&Path>,
+) -> TransferMetrics {
     let mut amount = web3::types::U256::exp10(17); // 0.1 eth
     amount.mul_assign(am);
 
-    let network = real_network(network);
-    // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
     let balance = client.balance(client.root_addr, None);
     info!("Balance of {:?}: {}", client.root_addr, balance);
 
-    let mut source_keys = if load {
-        let keys: Vec<_> = serde_json::from_str(std::fs::read_to_string("source_keys.001").unwrap().as_str()).unwrap();
-        keys
+    let mut key_file = if load {
+        KeyFile::load("source_keys.001").unwrap()
     } else {
         // check if the key file exists
         debug!("generating new source keys");
-        if std::fs::File::open("source_keys.001").is_ok() {
-            panic!("file \"source_keys.001\" already exists");
-        }
+        let follow = if std::fs::File::open("source_keys.001").is_ok() {
+            // new keys of the same seed follow the existing set, which is kept in the backup
+            match (seed, KeyFile::load("source_keys.001")) {
+                (Some(seed), Ok(KeyFile::Set(set))) if set.is_of(seed) => Some(set.end()),
+                _ => panic!("file \"source_keys.001\" already exists"),
+            }
+        } else {
+            None
+        };
         if amount.mul(count + 1) >= balance {
             panic!("Too large source account number, maximum {}", balance / amount);
         }
-        let source_keys = (0..count).map(|_| one_eth_key()).collect::<Vec<_>>();
-        let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write("source_keys.001", &data).unwrap();
+        // a key set keeps only the seed file and the range, the keys are derived when loaded
+        let key_file = match seed {
+            Some(seed) => KeyFile::Set(KeySet::create(seed, follow.unwrap_or(0), count as u32).unwrap()),
+            None => KeyFile::Keys((0..count).map(|_| one_eth_key()).collect()),
+        };
+        if follow.is_some() {
+            std::fs::rename("source_keys.001", ".source_keys.001.bak").unwrap();
+        }
+        key_file.save("source_keys.001").unwrap();
 
-        source_keys
+        key_file
     };
 
     // add more source keys and save them to file
-    if count as usize > source_keys.len() {
-        source_keys.resize_with(count as usize, one_eth_key);
+    if count as usize > key_file.len() {
+        key_file.extend(count as usize);
 
         std::fs::rename("source_keys.001", ".source_keys.001.bak").unwrap();
-        let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write("source_keys.001", &data).unwrap();
+        key_file.save("source_keys.001").unwrap();
     }
+    let source_keys = key_file.keys().unwrap();
 
     let total = source_keys.len();
     let source_accounts = source_keys
@@ -221,26 +252,332 @@ fn fund_accounts(
                 Some((from, amount))
             };
             if let Some(a) = account.as_ref() {
-                println!("{}/{} {:?}", idx + 1, total, a);
+                info!("{}/{} {:?}", idx + 1, total, a);
             }
             account
         })
         .collect::<Vec<_>>();
     // 1000 eth
-    let _metrics = client
+    client
         .distribution(1, None, &source_accounts, &Some(block_time), true, true)
+        .unwrap()
+}
+
+/// Drain every key of `source` into `to`, or the root account, and wait for the receipts.
+fn sweep_accounts(
+    client: &TestClient,
+    source: &Path,
+    to: Option<Address>,
+    block_time: u64,
+    max_threads: u64,
+) -> Vec<SweepMetric> {
+    let chain_id = client.chain_id().unwrap().as_u64();
+    let gas_price = client.gas_price().unwrap();
+    let to = to.unwrap_or(client.root_addr);
+    let wait_time = block_time * 3 + 1;
+
+    let source_keys = load_keys(source).unwrap();
+    check_parallel_args(max_threads);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(calc_pool_size(source_keys.len(), max_threads as usize))
+        .build()
         .unwrap();
-    // save metrics to file
-    //let data = serde_json::to_string(&metrics).unwrap();
-    //std::fs::write("metrics.001", &data).unwrap();
+
+    info!("sweeping {} source keys to {:?}...", source_keys.len(), to);
+    pool.install(|| {
+        source_keys
+            .par_iter()
+            .map(|kp| {
+                let source = (
+                    secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
+                    Address::from_str(kp.address.as_str()).unwrap(),
+                );
+                let mut metric = client.sweep(source, to, chain_id, gas_price);
+                if let Some(hash) = metric.hash {
+                    match client.wait_for_tx_receipt(hash, Duration::from_secs(1), wait_time) {
+                        (_, Some(receipt)) if receipt.status == Some(U64::from(1u64)) => metric.status = 1,
+                        (_, Some(_)) => metric.error = Some("tx failed".to_string()),
+                        (_, None) => metric.error = Some(format!("no receipt in {} seconds", wait_time)),
+                    }
+                }
+                debug!("{:?}", metric);
+                metric
+            })
+            .collect()
+    })
 }
 
-fn main() -> web3::Result<()> {
-    env_logger::init();
+/// save network info, metrics and summary of a run, failures are only logged
+fn save_run<M: Serialize + ?Sized>(run: &RunDir, network: &NetworkInfo, metrics: &M, summary: &[String]) {
+    let saved = run
+        .save(NETWORK_FILE, network)
+        .and_then(|_| run.save(METRICS_FILE, metrics))
+        .and_then(|_| run.save_summary(summary));
+    match saved {
+        Ok(_) => info!("run results saved to {}", run.path().display()),
+        Err(e) => error!("failed to save run results to {}: {}", run.path().display(), e),
+    }
+}
 
+/// one sender per endpoint, sharing the runtime, max in-flight sends and new blocks of `client`
+fn setup_senders(
+    client: &TestClient,
+    endpoints: Vec<Option<String>>,
+    timeout: Option<u64>,
+    token: Option<Erc20>,
+    chain_id: u64,
+    fee: TxFee,
+    latency: &Arc<LatencyTracker>,
+) -> Vec<Sender> {
+    let nonces = Arc::new(NonceManager::default());
+    endpoints
+        .into_iter()
+        .map(|url| {
+            let client = TestClient::setup_with_runtime(url.clone(), timeout, client.runtime())
+                .with_max_in_flight(client.concurrency.max())
+                .with_new_heads(client.new_heads());
+            Sender::new(
+                url.unwrap_or_default(),
+                client,
+                token.clone(),
+                chain_id,
+                fee,
+                latency.clone(),
+                nonces.clone(),
+            )
+        })
+        .collect()
+}
+
+/// targets of a source key in a run that reuses them, when no count is given
+const REUSED_TARGETS: u64 = 10;
+
+/// targets of every source key, a run that reuses them by duration or profile doesn't need `count`
+fn target_count(count: u64, reused: bool) -> u64 {
+    match count {
+        0 if reused => REUSED_TARGETS,
+        count => count,
+    }
+}
+
+/// Source keys with enough balance to send `count` txs of `amount`, each with `count` new targets
+fn prepare_sources(
+    client: &TestClient,
+    keys: &[KeyPair],
+    count: u64,
+    amount: U256,
+    check_balance: bool,
+) -> Vec<Source> {
+    keys.par_iter()
+        .filter_map(|kp| {
+            let (secret, address) = (
+                secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
+                Address::from_str(kp.address.as_str()).unwrap(),
+            );
+            let balance = if check_balance {
+                client.balance(address, None)
+            } else {
+                U256::MAX
+            };
+            if balance > amount.mul(count) {
+                let target = (0..count).map(|_| (random_address(), amount)).collect::<Vec<_>>();
+                debug!("account {:?} added to source pool", address);
+                Some((secret, address, target))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// follow new blocks of `client` with a `newHeads` subscription of `ws`, blocks are polled if it fails
+fn follow_new_heads(client: TestClient, ws: Option<&str>) -> TestClient {
+    let url = match ws {
+        Some(url) => url,
+        None => return client,
+    };
+    match client.runtime().block_on(NewHeads::subscribe(url)) {
+        Ok(heads) => {
+            info!("following new blocks of {}", url);
+            client.with_new_heads(Some(heads))
+        }
+        Err(e) => {
+            warn!("{}, polling new blocks instead", e);
+            client
+        }
+    }
+}
+
+/// build and sign `count` transactions for every source key, and save them to `corpus`
+fn presign(
+    client: &TestClient,
+    source: &Path,
+    count: u64,
+    token: Option<Address>,
+    fee: &FeeOpts,
+    max_threads: u64,
+    corpus: &Path,
+) {
+    let rt = client.runtime();
+    let chain_id = client.chain_id().unwrap().as_u64();
+    let fee = fee.tx_fee(client.gas_price().unwrap());
+    let token = token.map(|address| Erc20::new(Some(address)));
+    let target_amount = U256::exp10(16); // 0.01 eth, or 0.01 token
+
+    let source_keys = load_keys(source).unwrap();
+    check_parallel_args(max_threads);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(calc_pool_size(source_keys.len(), max_threads as usize))
+        .build()
+        .unwrap();
+
+    info!("signing {} transactions...", source_keys.len() as u64 * count);
+    let start = Instant::now();
+    let sources = pool.install(|| {
+        source_keys
+            .par_iter()
+            .filter_map(|kp| {
+                let (secret, address) = (
+                    secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
+                    Address::from_str(kp.address.as_str()).unwrap(),
+                );
+                let nonce = client.pending_nonce(address).or_else(|| {
+                    error!("failed to get nonce of {:?}, skipped", address);
+                    None
+                })?;
+                let txs = (0..count)
+                    .map(|i| {
+                        let target = (random_address(), target_amount);
+                        let nonce = nonce + i;
+                        let tx_object = match &token {
+                            Some(token) => {
+                                TestClient::erc20_transfer_tx(token, &target, Some(chain_id), None, Some(nonce))
+                            }
+                            None => Ok(TestClient::transfer_tx(&target, Some(chain_id), None, Some(nonce))),
+                        };
+                        tx_object
+                            .map(|mut tx_object| {
+                                fee.apply(&mut tx_object);
+                                tx_object
+                            })
+                            .and_then(|tx_object| rt.block_on(client.sign_async(tx_object, &secret)))
+                            .map(|signed| SignedTx {
+                                from: address,
+                                to: target.0,
+                                amount: target.1,
+                                nonce,
+                                hash: signed.transaction_hash,
+                                raw: signed.raw_transaction,
+                            })
+                    })
+                    // stop at the first failure, there should be no gap in nonces
+                    .map_while(|tx| tx.map_err(|e| error!("failed to sign tx of {:?}: {}", address, e)).ok())
+                    .collect::<Vec<_>>();
+                Some(txs)
+            })
+            .collect::<Vec<_>>()
+    });
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let corpus_data = Corpus {
+        chain_id,
+        fee,
+        token: token.and_then(|t| t.address),
+        sources,
+    };
+    info!(
+        "Presign summary: total,{},seconds,{:.3},rate,{:.3}",
+        corpus_data.len(),
+        elapsed,
+        corpus_data.len() as f64 / elapsed
+    );
+    corpus_data.save(corpus).unwrap();
+    info!("corpus saved to {}", corpus.display());
+}
+
+/// log the latency summary, then save the metrics, summary and result of a test run
+fn save_test_run(
+    run: &RunDir,
+    network_info: &NetworkInfo,
+    senders: &[Sender],
+    latency: &LatencyTracker,
+    mut summary: Vec<String>,
+    mut result: RunResult,
+) {
+    let latency_summary = latency
+        .summary()
+        .into_iter()
+        .map(|(name, l)| {
+            let line = format!(
+                "Latency summary: {},count,{},p50,{},p90,{},p99,{},max,{}",
+                name, l.count, l.p50, l.p90, l.p99, l.max
+            );
+            result.latency.insert(name.to_string(), l);
+            line
+        })
+        .collect::<Vec<_>>();
+    let kind_summary = kind_stats(senders)
+        .into_iter()
+        .map(|(kind, stats)| {
+            let l = stats.summary();
+            let line = format!(
+                "Kind summary: {},total,{}/{},p50,{},p90,{},p99,{},max,{}",
+                kind, stats.succeed, stats.total, l.p50, l.p90, l.p99, l.max
+            );
+            let kind_result = KindResult {
+                total: stats.total,
+                succeed: stats.succeed,
+                latency: l,
+            };
+            result.kinds.insert(kind.to_string(), kind_result);
+            line
+        })
+        .collect::<Vec<_>>();
+    result.errors = error_counts(senders);
+    let error_summary = result
+        .errors
+        .iter()
+        .map(|(category, count)| format!("Error summary: {},{}", category, count))
+        .collect::<Vec<_>>();
+    latency_summary
+        .iter()
+        .chain(kind_summary.iter())
+        .chain(error_summary.iter())
+        .for_each(|line| info!("{}", line));
+    summary.extend(latency_summary);
+    summary.extend(kind_summary);
+    summary.extend(error_summary);
+    if let Err(e) = run.save(ERRORS_FILE, &error_samples(senders)) {
+        error!("failed to save error samples: {}", e);
+    }
+
+    let metrics = transfer_metrics(senders, latency);
+    save_run(run, network_info, &metrics, &summary);
+    if let Err(e) = run.save(RESULT_FILE, &result) {
+        error!("failed to save test result: {}", e);
+    }
+}
+
+/// log to stderr, or to `LOG_FILE` while a dashboard takes the terminal
+fn init_logger(tui: bool) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if tui {
+        let file = std::fs::File::create(LOG_FILE).unwrap();
+        builder.target(env_logger::Target::Pipe(Box::new(file)));
+    }
+    builder.init();
+}
+
+fn main() -> web3::Result<()> {
     let cli = Cli::parse_args();
+    init_logger(matches!(
+        cli.command,
+        Some(Commands::Fund { tui: true, .. }) | Some(Commands::Test { tui: true, .. })
+    ));
+
     debug!("{:?}", cli);
     info!("logical cpus {}, physical cpus {}", log_cpus(), phy_cpus());
+    let command = cli.command.as_ref().map(|c| format!("{:?}", c)).unwrap_or_default();
 
     match &cli.command {
         Some(Commands::Fund {
@@ -251,16 +588,94 @@ fn main() -> web3::Result<()> {
             amount,
             load,
             redeposit,
+            seed,
+            tui,
+            metrics_listen,
+            output,
         }) => {
-            fund_accounts(
-                network.get_url().as_str(),
-                *timeout,
+            let run = RunDir::create(output, "fund", command).unwrap();
+            let endpoints = real_network(network.get_url().as_str());
+            // use first endpoint to fund accounts
+            let client = Arc::new(TestClient::setup(endpoints[0].clone(), *timeout));
+            let network_info = client.network_info();
+            let dashboard = tui.then(|| {
+                let client = client.clone();
+                Dashboard::start(
+                    "feth fund".to_string(),
+                    move || {
+                        let height = client.block_number().unwrap_or_default().as_u64();
+                        Snapshot::of_progress(&client.progress, height)
+                    },
+                    // the keys are saved before they're funded, nothing else to keep
+                    || std::process::exit(130),
+                )
+            });
+            let exporter = metrics_listen.map(|addr| {
+                let progress = client.progress.clone();
+                client
+                    .runtime()
+                    .spawn(metrics::serve(addr, move || metrics::render_progress(&progress)))
+            });
+            let metrics = fund_accounts(
+                &client,
                 *block_time,
                 *count,
                 *amount,
                 *load,
                 *redeposit,
+                seed.as_deref(),
             );
+            let summary = format!("Fund result summary: total,{}/{}", metrics.succeed, metrics.total);
+            info!("{}", summary);
+            if let Some(dashboard) = dashboard {
+                dashboard.stop();
+                println!("{}", summary);
+            }
+            save_run(&run, &network_info, &[metrics], &[summary]);
+            if let Some(exporter) = exporter {
+                exporter.abort();
+            }
+            Ok(())
+        }
+        Some(Commands::Sweep {
+            network,
+            timeout,
+            block_time,
+            source,
+            to,
+            max_threads,
+            output,
+        }) => {
+            let endpoints = real_network(network.get_url().as_str());
+            if endpoints.is_empty() {
+                error!("No valid endpoint found in {}", network.get_url());
+                return Ok(());
+            }
+            let run = RunDir::create(output, "sweep", command).unwrap();
+            let client = TestClient::setup(endpoints[0].clone(), *timeout);
+            let network_info = client.network_info();
+            let metrics = sweep_accounts(&client, source, *to, *block_time, *max_threads);
+
+            let swept = metrics.iter().filter(|m| m.status == 1).collect::<Vec<_>>();
+            let empty = metrics.iter().filter(|m| m.status == 0).count();
+            let failed = metrics.iter().filter(|m| m.status > 1).collect::<Vec<_>>();
+            let recovered = swept.iter().fold(U256::zero(), |total, m| total + m.amount);
+            let mut summary = vec![format!(
+                "Sweep result summary: keys,{},swept,{},empty,{},failed,{},recovered,{} wei({:.4} eth)",
+                metrics.len(),
+                swept.len(),
+                empty,
+                failed.len(),
+                recovered,
+                recovered.low_u128() as f64 / 1e18,
+            )];
+            summary.extend(
+                failed
+                    .iter()
+                    .map(|m| format!("Sweep failed: {:?},{}", m.from, m.error.as_deref().unwrap_or("unknown"))),
+            );
+            summary.iter().for_each(|line| info!("{}", line));
+            save_run(&run, &network_info, &metrics, &summary);
             Ok(())
         }
         Some(Commands::Info {
@@ -299,28 +714,62 @@ fn main() -> web3::Result<()> {
         }
         Some(Commands::Test {
             network,
-            mode: _,
+            mode,
             delay,
             max_threads,
             count,
             source,
             block_time,
             timeout,
+            ws,
             need_retry,
             check_balance,
+            rate,
+            profile,
+            fee,
+            guard,
+            pipeline,
+            max_in_flight,
+            duration,
+            metrics_listen,
+            report_interval,
+            tui,
+            output,
+            ..
         }) => {
+            // both are required unless set by the scenario, checked in `Cli::parse_args`
+            let (network, mode) = (network.as_ref().unwrap(), mode.as_ref().unwrap());
             let max_par = *max_threads;
             let source_file = source;
-            let _block_time = Some(*block_time);
+            let block_time = Some(*block_time);
             let timeout = Some(*timeout);
             let count = *count;
             let _need_retry = *need_retry;
+            let max_in_flight = *max_in_flight;
 
-            let source_keys: Vec<KeyPair> =
-                serde_json::from_str(std::fs::read_to_string(source_file).unwrap().as_str()).unwrap();
-            let target_amount = web3::types::U256::exp10(16); // 0.01 eth
+            let source_keys = load_keys(source_file).unwrap();
+            let target_amount = web3::types::U256::exp10(16); // 0.01 eth, or 0.01 token in contract mode
 
             check_parallel_args(max_par);
+            if max_in_flight == 0 {
+                error!("Invalid max in-flight requests: 0");
+                return Ok(());
+            }
+            if *pipeline == 0 {
+                error!("Invalid pipeline: 0");
+                return Ok(());
+            }
+            if *duration == Some(0) || *report_interval == 0 {
+                error!("Invalid duration or report interval: 0");
+                return Ok(());
+            }
+            let targets = target_count(count, duration.is_some() || profile.is_some());
+            if targets == 0 {
+                error!("Invalid count: 0, only a soak test or a profile can do without it");
+                return Ok(());
+            }
+            let run = RunDir::create(output, "test", command).unwrap();
+            let mut summary = vec![];
 
             let max_pool_size = calc_pool_size(source_keys.len(), max_par as usize);
             rayon::ThreadPoolBuilder::new()
@@ -329,8 +778,17 @@ fn main() -> web3::Result<()> {
                 .unwrap();
             info!("thread pool size {}", max_pool_size);
 
-            let url = network.get_url();
-            let client = Arc::new(TestClient::setup(Some(url), timeout));
+            // source keys will be divided equally to each endpoint
+            let endpoints = real_network(network.get_url().as_str());
+            if endpoints.is_empty() {
+                error!("No valid endpoint found in {}", network.get_url());
+                return Ok(());
+            }
+            info!("endpoints: {:?}", endpoints);
+            let client = TestClient::setup(endpoints[0].clone(), timeout).with_max_in_flight(max_in_flight);
+            let client = follow_new_heads(client, ws.as_deref());
+            let rt = client.runtime();
+            let network_info = client.network_info();
 
             let chain_id = client.chain_id().unwrap().as_u64();
             let gas_price = client.gas_price().unwrap();
@@ -340,90 +798,516 @@ fn main() -> web3::Result<()> {
             info!("frc20 code:   {:?}", client.frc20_code().unwrap());
 
             info!("preparing test data...");
-            let source_keys = source_keys
-                .par_iter()
-                .filter_map(|kp| {
-                    let (secret, address) = (
-                        secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
-                        Address::from_str(kp.address.as_str()).unwrap(),
-                    );
-                    let balance = if *check_balance {
-                        client.balance(address, None)
-                    } else {
-                        U256::MAX
-                    };
-                    if balance > target_amount.mul(count) {
-                        let target = (0..count)
-                            .map(|_| {
-                                (
-                                    Address::from_str(one_eth_key().address.as_str()).unwrap(),
-                                    target_amount,
-                                )
-                            })
-                            .collect::<Vec<_>>();
-                        debug!("account {:?} added to source pool", address);
-                        Some((secret, address, target))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
+            let source_keys = prepare_sources(&client, &source_keys, targets, target_amount, *check_balance);
 
-            if count == 0 || source_keys.is_empty() {
+            if source_keys.is_empty() {
                 error!("Not enough sufficient source accounts or target accounts, skipped.");
                 return Ok(());
             }
 
-            let total_succeed = AtomicU64::new(0);
-            let concurrences = if source_keys.len() > max_pool_size {
-                max_pool_size
-            } else {
-                source_keys.len()
+            let mix = match mode {
+                TestMode::Basic => Mix::single(Kind::Transfer),
+                TestMode::Contract => Mix::single(Kind::Token),
+                TestMode::Mix(mix) => mix.clone(),
             };
-
-            // one-thread per source key
-            info!("starting tests...");
-            let start_height = client.block_number().unwrap();
-            let mut last_height = start_height;
-            let total = source_keys.len() * count as usize;
-            let now = std::time::Instant::now();
-            for r in 0..count {
-                loop {
-                    let current = client.block_number().unwrap();
-                    if current > last_height {
-                        last_height = current;
-                        break;
-                    } else {
-                        std::thread::sleep(Duration::from_secs(1));
+            let token = match mix.needs_token() {
+                false => None,
+                true => {
+                    info!("deploying test token...");
+                    let supply = target_amount.mul(source_keys.len() as u64 * count);
+                    let token = client.deploy_erc20(supply, &block_time).unwrap();
+                    let targets = source_keys
+                        .iter()
+                        .map(|(_, address, _)| (*address, target_amount.mul(count)))
+                        .collect::<Vec<_>>();
+                    let metrics = client.erc20_distribution(&token, &targets, &block_time).unwrap();
+                    if metrics.succeed < metrics.total {
+                        error!("token distribution: {}/{} succeeded", metrics.succeed, metrics.total);
                     }
+                    Some(token)
                 }
-                let now = std::time::Instant::now();
-                source_keys.par_iter().for_each(|(source, address, targets)| {
-                    let target = targets.get(r as usize).unwrap();
-                    if let Some(nonce) = client.pending_nonce(*address) {
-                        if client
-                            .distribution_simple(source, target, Some(chain_id), Some(gas_price), Some(nonce))
-                            .is_ok()
-                        {
-                            total_succeed.fetch_add(1, Relaxed);
-                        }
-                    }
+            };
+            // a soak test keeps no per-tx records, only the aggregated ones
+            let latency = match duration {
+                Some(_) => LatencyTracker::default().without_inclusion(),
+                None => LatencyTracker::default(),
+            };
+            let latency = Arc::new(latency);
+            let fee = fee.tx_fee(gas_price);
+            info!("tx fee:       {:?}", fee);
+            let mut senders = setup_senders(&client, endpoints, timeout, token, chain_id, fee, &latency);
+            if duration.is_some() {
+                senders = senders.into_iter().map(Sender::without_records).collect();
+            }
+            let guard = Arc::new(guard.guard());
+            let senders = senders
+                .into_iter()
+                .map(|s| s.with_mix(mix.clone()).with_guard(guard.clone()))
+                .collect::<Vec<_>>();
+            let senders = Arc::new(senders);
+
+            let profile = match (profile, *rate, *duration) {
+                (Some(profile), _, Some(secs)) => Some((profile.repeat(secs), None)),
+                (Some(profile), _, None) => Some((profile.clone(), None)),
+                (None, Some(0), _) => {
+                    error!("Invalid rate: 0");
+                    return Ok(());
+                }
+                (None, Some(rate), Some(secs)) => Some((Profile::steady(rate, secs), None)),
+                (None, Some(rate), None) => {
+                    let total = source_keys.len() as u64 * count;
+                    Some((Profile::constant(rate, total), Some(total)))
+                }
+                (None, None, _) => None,
+            };
+            // a soak test or a profile doesn't say how many txs a key sends
+            let per_key = match (&profile, duration) {
+                (None, None) | (Some((_, Some(_))), None) => count,
+                _ => 0,
+            };
+            let concurrences = source_keys.len().min(max_in_flight);
+            let sources = Arc::new(source_keys);
+
+            let start_height = client.block_number().unwrap();
+            let watcher = {
+                let latency = latency.clone();
+                let client = senders[0].client.clone();
+                let timeout = Duration::from_secs(block_time.unwrap_or(BLOCK_TIME) * 3 + 1);
+                rt.spawn(async move { latency.watch_blocks(&client, start_height, timeout).await })
+            };
+            let reporter = duration.map(|_| {
+                let interval = Duration::from_secs(*report_interval);
+                rt.spawn(report_progress(senders.clone(), latency.clone(), interval))
+            });
+            let exporter = metrics_listen.map(|addr| {
+                let (senders, latency) = (senders.clone(), latency.clone());
+                rt.spawn(metrics::serve(addr, move || metrics::render(&senders, &latency)))
+            });
+            let dashboard = tui.then(|| {
+                let senders = senders.clone();
+                let latency = latency.clone();
+                let guard = guard.clone();
+                Dashboard::start(
+                    "feth test".to_string(),
+                    move || Snapshot::of_senders(&senders, &latency, per_key),
+                    // stop like a tripped guard, so the partial results are still saved
+                    move || guard.trip("interrupted".to_string()),
+                )
+            });
+            let guarding = guard
+                .needs_watch()
+                .then(|| rt.spawn(guard.clone().watch(senders.clone(), sources.clone())));
+
+            let mut result = if let Some((profile, limit)) = profile {
+                info!(
+                    "starting tests with profile {:?}, {} seconds...",
+                    profile.stages,
+                    profile.duration()
+                );
+                let stats = rt.block_on(open_loop(senders.clone(), sources, &profile, limit));
+                let end_height = client.block_number().unwrap();
+                stats.stages.iter().enumerate().for_each(|(idx, stage)| {
+                    summary.push(format!(
+                        "Stage summary: {},{},total,{}/{},reads,{},offered,{:.3},achieved,{:.3},late,{},seconds,{},errors,{}",
+                        idx + 1,
+                        stage.stage,
+                        stage.succeed,
+                        stage.total,
+                        stage.reads,
+                        stage.offered_rate(),
+                        stage.achieved_rate(),
+                        stage.late,
+                        stage.secs,
+                        stage.errors,
+                    ));
                 });
-                let elapsed = now.elapsed().as_secs();
-                info!("round {}/{} time {}", r + 1, count, elapsed);
-                std::thread::sleep(Duration::from_secs(*delay));
+                summary.push(format!(
+                    "Test result summary: total,{}/{},reads,{},offered,{:.3},achieved,{:.3},late,{},seconds,{:.3},height,{},{}",
+                    stats.succeed,
+                    stats.total,
+                    stats.reads,
+                    stats.offered_rate(),
+                    stats.achieved_rate(),
+                    stats.late,
+                    stats.elapsed_secs,
+                    start_height,
+                    end_height,
+                ));
+                RunResult {
+                    total: stats.total,
+                    succeed: stats.succeed,
+                    elapsed_secs: stats.elapsed_secs,
+                    end_height: end_height.as_u64(),
+                    ..Default::default()
+                }
+            } else {
+                info!("starting tests...");
+                let stats = rt.block_on(round_loop(
+                    senders.clone(),
+                    sources,
+                    duration.map_or(RunLimit::Count(count), |secs| {
+                        RunLimit::Duration(Duration::from_secs(secs))
+                    }),
+                    *pipeline,
+                    *delay,
+                    start_height,
+                ));
+                let end_height = client.block_number().unwrap();
+
+                let avg = stats.total as f64 / stats.elapsed_secs;
+                summary.push(format!(
+                    "Test result summary: total,{}/{},reads,{},concurrency,{},TPS,{:.3},seconds,{:.3},height,{},{}",
+                    stats.succeed,
+                    stats.total,
+                    stats.reads,
+                    concurrences,
+                    avg,
+                    stats.elapsed_secs,
+                    start_height,
+                    end_height,
+                ));
+                RunResult {
+                    total: stats.total,
+                    succeed: stats.succeed,
+                    elapsed_secs: stats.elapsed_secs,
+                    end_height: end_height.as_u64(),
+                    ..Default::default()
+                }
+            };
+            if let Some(reporter) = reporter {
+                reporter.abort();
+            }
+            if let Some(guarding) = guarding {
+                guarding.abort();
             }
+            if let Some(reason) = guard.reason() {
+                summary.push(format!("Test aborted: {}", reason));
+                result.aborted = Some(reason);
+            }
+            result.start_height = start_height.as_u64();
+            summary.extend(endpoint_summary(&senders, result.elapsed_secs));
+            summary.iter().for_each(|line| info!("{}", line));
 
-            let elapsed = now.elapsed().as_secs();
-            let end_height = client.block_number().unwrap();
+            latency.finish();
+            info!("waiting for {} txs to be included...", latency.pending());
+            rt.block_on(watcher).unwrap();
+            if let Some(dashboard) = dashboard {
+                dashboard.stop();
+                summary.iter().for_each(|line| println!("{}", line));
+            }
+            save_test_run(&run, &network_info, &senders, &latency, summary, result);
+            // the final values stay scrapable until the results are saved
+            if let Some(exporter) = exporter {
+                exporter.abort();
+            }
+            Ok(())
+        }
+        Some(Commands::Coordinate {
+            network,
+            workers,
+            source,
+            count,
+            pipeline,
+            delay,
+            duration,
+            max_in_flight,
+            check_balance,
+            fee,
+            block_time,
+            timeout,
+            output,
+        }) => {
+            let keys = KeyFile::load(source).unwrap();
+            if keys.is_empty() || (*count == 0 && duration.is_none()) {
+                error!("Not enough source keys or transactions, skipped.");
+                return Ok(());
+            }
+            if *pipeline == 0 || *max_in_flight == 0 || *duration == Some(0) {
+                error!("Invalid pipeline, max in-flight requests or duration: 0");
+                return Ok(());
+            }
+            if let KeyFile::Keys(_) = keys {
+                warn!("private keys of {} are sent to workers in plain text", source.display());
+            }
+            let endpoints = real_network(network.get_url().as_str());
+            if endpoints.is_empty() {
+                error!("No valid endpoint found in {}", network.get_url());
+                return Ok(());
+            }
+            let run = RunDir::create(output, "coordinate", command).unwrap();
+            let client = TestClient::setup(endpoints[0].clone(), Some(*timeout));
+            let network_info = client.network_info();
+            let job = Job {
+                network: network.get_url(),
+                timeout: *timeout,
+                block_time: *block_time,
+                keys: KeyFile::Keys(vec![]),
+                count: *count,
+                pipeline: *pipeline,
+                delay: *delay,
+                max_in_flight: *max_in_flight,
+                duration: *duration,
+                check_balance: *check_balance,
+                fee: fee.tx_fee(network_info.gas_price),
+            };
+            let start_height = client.block_number().unwrap().as_u64();
+            let reports = match cluster::coordinate(workers, job, keys) {
+                Ok(reports) => reports,
+                Err(e) => {
+                    error!("distributed test failed: {}", e);
+                    return Ok(());
+                }
+            };
 
-            let avg = total as f64 / elapsed as f64;
-            info!(
-                "Test result summary: total,{:?}/{},concurrency,{},TPS,{:.3},seconds,{},height,{},{}",
-                total_succeed, total, concurrences, avg, elapsed, start_height, end_height,
+            let result = cluster::merge(&reports, start_height);
+            let mut summary = reports
+                .iter()
+                .map(|(worker, report)| {
+                    format!(
+                        "Worker summary: {},total,{}/{},seconds,{:.3},height,{}",
+                        worker, report.succeed, report.total, report.elapsed_secs, report.end_height
+                    )
+                })
+                .collect::<Vec<_>>();
+            summary.push(format!(
+                "Test result summary: total,{}/{},workers,{}/{},TPS,{:.3},seconds,{:.3},height,{},{}",
+                result.succeed,
+                result.total,
+                reports.len(),
+                workers.len(),
+                result.total as f64 / result.elapsed_secs,
+                result.elapsed_secs,
+                result.start_height,
+                result.end_height,
+            ));
+            summary.extend(result.latency.iter().map(|(name, l)| {
+                format!(
+                    "Latency summary: {},count,{},p50,{},p90,{},p99,{},max,{}",
+                    name, l.count, l.p50, l.p90, l.p99, l.max
+                )
+            }));
+            summary.extend(
+                result
+                    .errors
+                    .iter()
+                    .map(|(category, count)| format!("Error summary: {},{}", category, count)),
             );
+            summary.iter().for_each(|line| info!("{}", line));
+
+            if let Err(e) = run.save(ERRORS_FILE, &cluster::merge_errors(&reports)) {
+                error!("failed to save error samples: {}", e);
+            }
+            let metrics = reports
+                .into_iter()
+                .flat_map(|(_, report)| report.metrics)
+                .collect::<Vec<_>>();
+            save_run(&run, &network_info, &metrics, &summary);
+            if let Err(e) = run.save(RESULT_FILE, &result) {
+                error!("failed to save test result: {}", e);
+            }
+            Ok(())
+        }
+        Some(Commands::Worker { listen, seed }) => {
+            cluster::work(*listen, seed.clone());
+            Ok(())
+        }
+        Some(Commands::Presign {
+            network,
+            timeout,
+            source,
+            count,
+            token,
+            fee,
+            max_threads,
+            corpus,
+        }) => {
+            let network = real_network(network.get_url().as_str());
+            let client = TestClient::setup(network[0].clone(), *timeout);
+            presign(&client, source, *count, *token, fee, *max_threads, corpus);
+            Ok(())
+        }
+        Some(Commands::Replay {
+            network,
+            corpus,
+            block_time,
+            timeout,
+            ws,
+            max_in_flight,
+            metrics_listen,
+            output,
+        }) => {
+            let timeout = Some(*timeout);
+            if *max_in_flight == 0 {
+                error!("Invalid max in-flight requests: 0");
+                return Ok(());
+            }
+            let run = RunDir::create(output, "replay", command).unwrap();
+            let mut summary = vec![];
+
+            let corpus = Corpus::load(corpus).unwrap();
+            let endpoints = real_network(network.get_url().as_str());
+            if endpoints.is_empty() {
+                error!("No valid endpoint found in {}", network.get_url());
+                return Ok(());
+            }
+            info!("endpoints: {:?}", endpoints);
+            let client = TestClient::setup(endpoints[0].clone(), timeout).with_max_in_flight(*max_in_flight);
+            let client = follow_new_heads(client, ws.as_deref());
+            let rt = client.runtime();
+            let network_info = client.network_info();
+            if network_info.chain_id != U256::from(corpus.chain_id) {
+                error!(
+                    "Chain id mismatch, corpus {} network {}",
+                    corpus.chain_id, network_info.chain_id
+                );
+                return Ok(());
+            }
+
+            let latency = Arc::new(LatencyTracker::default());
+            let token = corpus.token.map(|address| Erc20::new(Some(address)));
+            let (chain_id, fee) = (corpus.chain_id, corpus.fee);
+            let senders = setup_senders(&client, endpoints, timeout, token, chain_id, fee, &latency);
+            let senders = Arc::new(senders);
+
+            let start_height = client.block_number().unwrap();
+            let watcher = {
+                let latency = latency.clone();
+                let client = senders[0].client.clone();
+                let timeout = Duration::from_secs(*block_time * 3 + 1);
+                rt.spawn(async move { latency.watch_blocks(&client, start_height, timeout).await })
+            };
+            let exporter = metrics_listen.map(|addr| {
+                let (senders, latency) = (senders.clone(), latency.clone());
+                rt.spawn(metrics::serve(addr, move || metrics::render(&senders, &latency)))
+            });
+
+            info!("replaying {} transactions...", corpus.len());
+            let stats = rt.block_on(replay(senders.clone(), Arc::new(corpus)));
+            let end_height = client.block_number().unwrap();
+            summary.push(format!(
+                "Test result summary: total,{}/{},TPS,{:.3},seconds,{:.3},height,{},{}",
+                stats.succeed,
+                stats.total,
+                stats.total as f64 / stats.elapsed_secs,
+                stats.elapsed_secs,
+                start_height,
+                end_height,
+            ));
+            let result = RunResult {
+                total: stats.total,
+                succeed: stats.succeed,
+                elapsed_secs: stats.elapsed_secs,
+                start_height: start_height.as_u64(),
+                end_height: end_height.as_u64(),
+                ..Default::default()
+            };
+            summary.extend(endpoint_summary(&senders, result.elapsed_secs));
+            summary.iter().for_each(|line| info!("{}", line));
+
+            latency.finish();
+            info!("waiting for {} txs to be included...", latency.pending());
+            rt.block_on(watcher).unwrap();
+            save_test_run(&run, &network_info, &senders, &latency, summary, result);
+            if let Some(exporter) = exporter {
+                exporter.abort();
+            }
             Ok(())
         }
+        Some(Commands::BenchRpc {
+            network,
+            methods,
+            concurrency,
+            duration,
+            count,
+            timeout,
+            output,
+        }) => {
+            if *concurrency == 0 || *duration == 0 || *count == Some(0) {
+                error!("Invalid concurrency, duration or count: 0");
+                return Ok(());
+            }
+            let run = RunDir::create(output, "bench", command).unwrap();
+            let endpoints = real_network(network.get_url().as_str());
+            if endpoints.is_empty() {
+                error!("No valid endpoint found in {}", network.get_url());
+                return Ok(());
+            }
+            info!("endpoints: {:?}", endpoints);
+            let timeout = Some(*timeout);
+            let client = TestClient::setup(endpoints[0].clone(), timeout);
+            let rt = client.runtime();
+            let network_info = client.network_info();
+            let clients = endpoints
+                .into_iter()
+                .map(|url| TestClient::setup_with_runtime(url, timeout, rt.clone()))
+                .collect::<Vec<_>>();
+            let limit = count.map_or(RunLimit::Duration(Duration::from_secs(*duration)), RunLimit::Count);
+
+            info!("starting benchmark, {} workers...", concurrency);
+            let (stats, elapsed_secs) = rt.block_on(bench_rpc(Arc::new(clients), methods.clone(), *concurrency, limit));
+            let mut result = RunResult {
+                elapsed_secs,
+                ..Default::default()
+            };
+            let mut summary = stats
+                .into_iter()
+                .map(|(method, s)| {
+                    let l = s.summary();
+                    result.total += s.total;
+                    result.succeed += s.succeed;
+                    let line = format!(
+                        "Method summary: {},total,{}/{},rps,{:.3},p50,{},p90,{},p99,{},max,{}",
+                        method,
+                        s.succeed,
+                        s.total,
+                        s.succeed as f64 / elapsed_secs,
+                        l.p50,
+                        l.p90,
+                        l.p99,
+                        l.max
+                    );
+                    let kind_result = KindResult {
+                        total: s.total,
+                        succeed: s.succeed,
+                        latency: l,
+                    };
+                    result.kinds.insert(method.to_string(), kind_result);
+                    line
+                })
+                .collect::<Vec<_>>();
+            summary.push(format!(
+                "Bench result summary: total,{}/{},rps,{:.3},seconds,{:.3},concurrency,{}",
+                result.succeed,
+                result.total,
+                result.tps(),
+                elapsed_secs,
+                concurrency
+            ));
+            summary.iter().for_each(|line| info!("{}", line));
+            save_run(&run, &network_info, &[] as &[TransferMetrics], &summary);
+            if let Err(e) = run.save(RESULT_FILE, &result) {
+                error!("failed to save bench result: {}", e);
+            }
+            Ok(())
+        }
+        Some(Commands::Report {
+            command:
+                ReportCommands::Compare {
+                    base,
+                    current,
+                    threshold,
+                },
+        }) => match Cli::compare(base, current, *threshold) {
+            Ok(false) => Ok(()),
+            Ok(true) => {
+                error!("regression found, threshold {}%", threshold);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                error!("failed to compare {:?} with {:?}: {}", current, base, e);
+                std::process::exit(2);
+            }
+        },
         None => Ok(()),
     }
 }
@@ -0,0 +1,234 @@
+use crate::load::{kind_stats, Sender};
+use feth::{
+    latency::{LatencyTracker, SIGN_STAGE},
+    progress::Progress,
+};
+use hdrhistogram::Histogram;
+use log::{error, info};
+use std::{fmt::Write, net::SocketAddr, sync::atomic::Ordering::Relaxed, sync::Arc};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// upper bounds of the latency buckets, milliseconds
+const BUCKETS: [u64; 12] = [10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000, 60000];
+/// largest request accepted from a scraper
+const MAX_REQUEST: usize = 8192;
+
+/// Render the counters of `senders` and the latency of `latency` in the Prometheus text format
+pub(crate) fn render(senders: &[Sender], latency: &LatencyTracker) -> String {
+    let mut out = String::new();
+
+    header(&mut out, "feth_sent_total", "counter", "sends by endpoint");
+    header(
+        &mut out,
+        "feth_succeeded_total",
+        "counter",
+        "succeeded sends by endpoint",
+    );
+    senders.iter().for_each(|sender| {
+        let succeed = sender.succeed.load(Relaxed);
+        let failed = sender.failed.load(Relaxed);
+        let _ = writeln!(
+            out,
+            "feth_sent_total{{endpoint=\"{}\"}} {}",
+            sender.url,
+            succeed + failed
+        );
+        let _ = writeln!(out, "feth_succeeded_total{{endpoint=\"{}\"}} {}", sender.url, succeed);
+    });
+
+    header(
+        &mut out,
+        "feth_failed_total",
+        "counter",
+        "failed sends by endpoint and error category",
+    );
+    senders.iter().for_each(|sender| {
+        sender.error_counts().iter().for_each(|(category, count)| {
+            let _ = writeln!(
+                out,
+                "feth_failed_total{{endpoint=\"{}\",category=\"{}\"}} {}",
+                sender.url, category, count
+            );
+        })
+    });
+
+    header(&mut out, "feth_in_flight", "gauge", "requests in flight by endpoint");
+    header(
+        &mut out,
+        "feth_concurrency_limit",
+        "gauge",
+        "allowed requests in flight by endpoint",
+    );
+    senders.iter().for_each(|sender| {
+        let concurrency = &sender.client.concurrency;
+        let _ = writeln!(
+            out,
+            "feth_in_flight{{endpoint=\"{}\"}} {}",
+            sender.url,
+            concurrency.in_flight()
+        );
+        let _ = writeln!(
+            out,
+            "feth_concurrency_limit{{endpoint=\"{}\"}} {}",
+            sender.url,
+            concurrency.limit()
+        );
+    });
+
+    let resyncs = senders.first().map_or(0, |s| s.nonces.resyncs());
+    header(
+        &mut out,
+        "feth_nonce_resyncs_total",
+        "counter",
+        "local nonces dropped after InvalidNonce or a rejected send out of order",
+    );
+    let _ = writeln!(out, "feth_nonce_resyncs_total {}", resyncs);
+
+    header(
+        &mut out,
+        "feth_block_height",
+        "gauge",
+        "the last block checked for included txs",
+    );
+    let _ = writeln!(out, "feth_block_height {}", latency.height());
+
+    header(
+        &mut out,
+        "feth_tx_latency_seconds",
+        "histogram",
+        "tx latency from signing by stage",
+    );
+    latency.histograms().iter().for_each(|(stage, h)| {
+        let per_ms = if *stage == SIGN_STAGE { 1000 } else { 1 };
+        histogram(
+            &mut out,
+            "feth_tx_latency_seconds",
+            &format!("stage=\"{}\"", stage),
+            h,
+            per_ms,
+        );
+    });
+
+    header(
+        &mut out,
+        "feth_request_latency_seconds",
+        "histogram",
+        "response time of succeeded requests by kind",
+    );
+    kind_stats(senders).iter().for_each(|(kind, stats)| {
+        histogram(
+            &mut out,
+            "feth_request_latency_seconds",
+            &format!("kind=\"{}\"", kind),
+            &stats.latency,
+            1,
+        );
+    });
+    out
+}
+
+/// Render the counters of a fund run in the Prometheus text format
+pub(crate) fn render_progress(progress: &Progress) -> String {
+    let mut out = String::new();
+    header(&mut out, "feth_sent_total", "counter", "txs accepted by the node");
+    let _ = writeln!(out, "feth_sent_total {}", progress.sends());
+    header(
+        &mut out,
+        "feth_succeeded_total",
+        "counter",
+        "txs included and succeeded",
+    );
+    let _ = writeln!(out, "feth_succeeded_total {}", progress.succeeded());
+    header(
+        &mut out,
+        "feth_failed_total",
+        "counter",
+        "failed targets by error category",
+    );
+    progress.errors().iter().for_each(|(category, count)| {
+        let _ = writeln!(out, "feth_failed_total{{category=\"{}\"}} {}", category, count);
+    });
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// a histogram in seconds, `per_ms` is the number of its units in a millisecond
+fn histogram(out: &mut String, name: &str, labels: &str, h: &Histogram<u64>, per_ms: u64) {
+    BUCKETS.iter().for_each(|le| {
+        let count = h.count_between(0, *le * per_ms);
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            name,
+            labels,
+            *le as f64 / 1000.0,
+            count
+        );
+    });
+    let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, h.len());
+    let sum = h.mean() * h.len() as f64 / (1000 * per_ms) as f64;
+    let _ = writeln!(out, "{}_sum{{{}}} {:.3}", name, labels, sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, h.len());
+}
+
+/// Serve `/metrics` rendered by `render` on `addr` until aborted
+pub(crate) async fn serve<F>(addr: SocketAddr, render: F)
+where
+    F: Fn() -> String + Send + Sync + 'static,
+{
+    let render = Arc::new(render);
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("failed to listen on {}: {}", addr, e);
+            return;
+        }
+    };
+    info!("serving metrics on http://{}/metrics", addr);
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let render = render.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = respond(stream, render.as_ref()).await {
+                        error!("failed to serve metrics: {}", e);
+                    }
+                });
+            }
+            Err(e) => error!("failed to accept a metrics connection: {}", e),
+        }
+    }
+}
+
+/// answer one request, the connection is closed afterwards
+async fn respond(mut stream: TcpStream, render: &(dyn Fn() -> String + Send + Sync)) -> std::io::Result<()> {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let (status, body) = match request.split_whitespace().take(2).collect::<Vec<_>>().as_slice() {
+        ["GET", "/metrics"] => ("200 OK", render()),
+        _ => ("404 Not Found", "not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,69 @@
+use crate::TestClient;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+};
+use web3::types::{Address, U256};
+
+/// Hands out sequential nonces of accounts without asking the node every time.
+///
+/// The first nonce of an account is its pending nonce, the following ones are counted locally,
+/// so an account can have many transactions in flight. Call `resync` after an `InvalidNonce`
+/// error, the next nonce will be fetched from the node again, and `release` after any other
+/// failed send so its nonce doesn't leave a gap.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    nonces: Mutex<HashMap<Address, U256>>,
+    resyncs: AtomicU64,
+}
+
+impl NonceManager {
+    /// the nonce for the next transaction of `address`
+    pub async fn next(&self, client: &TestClient, address: Address) -> Option<U256> {
+        if let Some(nonce) = self.take(address, None) {
+            return Some(nonce);
+        }
+        let pending = client.pending_nonce_async(address).await?;
+        self.take(address, Some(pending))
+    }
+
+    /// forget the local nonce of `address`
+    pub fn resync(&self, address: Address) {
+        self.resyncs.fetch_add(1, Relaxed);
+        self.nonces.lock().unwrap().remove(&address);
+    }
+
+    /// give back `nonce` of a tx the node didn't accept, it's handed out again if it's the last one
+    /// of `address`, otherwise the local nonce is forgotten to fill the gap
+    pub fn release(&self, address: Address, nonce: U256) {
+        let mut nonces = self.nonces.lock().unwrap();
+        match nonces.get_mut(&address) {
+            Some(next) if *next == nonce + U256::one() => *next = nonce,
+            Some(_) => {
+                self.resyncs.fetch_add(1, Relaxed);
+                nonces.remove(&address);
+            }
+            None => {}
+        }
+    }
+
+    /// times a local nonce was forgotten so far
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs.load(Relaxed)
+    }
+
+    fn take(&self, address: Address, pending: Option<U256>) -> Option<U256> {
+        let mut nonces = self.nonces.lock().unwrap();
+        let nonce = match pending {
+            // another task may have synced it in the meantime
+            Some(pending) => nonces.entry(address).or_insert(pending),
+            None => nonces.get_mut(&address)?,
+        };
+        let next = *nonce;
+        *nonce += U256::one();
+        Some(next)
+    }
+}
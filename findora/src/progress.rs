@@ -0,0 +1,93 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Mutex,
+    },
+};
+use web3::types::Address;
+
+/// Live counters of `distribution`, so a fund run can be watched while it's going.
+///
+/// A target is sent once its tx is accepted by the node, and succeeded once the receipt says so.
+/// Every target fails at most once, by the final outcome rather than every retry.
+#[derive(Debug, Default)]
+pub struct Progress {
+    sent: AtomicU64,
+    /// targets without any accepted tx
+    unsent: AtomicU64,
+    succeed: AtomicU64,
+    failed: AtomicU64,
+    /// failed targets by error category
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    /// targets with their sent and succeeded txs
+    targets: Mutex<BTreeMap<Address, (u64, u64)>>,
+}
+
+impl Progress {
+    /// targets to be sent, nothing is sent to them yet
+    pub fn start(&self, targets: &[Address]) {
+        let mut all = self.targets.lock().unwrap();
+        targets.iter().for_each(|target| {
+            all.entry(*target).or_default();
+        });
+    }
+
+    /// the tx to `target` is accepted by the node
+    pub fn sent(&self, target: Address) {
+        self.sent.fetch_add(1, Relaxed);
+        self.targets.lock().unwrap().entry(target).or_default().0 += 1;
+    }
+
+    /// no tx to a target was accepted, `category` is of the last error
+    pub fn unsent(&self, category: &'static str) {
+        self.unsent.fetch_add(1, Relaxed);
+        self.fail(category);
+    }
+
+    /// the tx to `target` is included, `Some(ok)` if it succeeded, `None` if no receipt came
+    pub fn confirmed(&self, target: Address, ok: Option<bool>) {
+        match ok {
+            Some(true) => {
+                self.succeed.fetch_add(1, Relaxed);
+                self.targets.lock().unwrap().entry(target).or_default().1 += 1;
+            }
+            Some(false) => self.fail("reverted"),
+            None => self.fail("timeout"),
+        }
+    }
+
+    fn fail(&self, category: &'static str) {
+        self.failed.fetch_add(1, Relaxed);
+        *self.errors.lock().unwrap().entry(category).or_default() += 1;
+    }
+
+    /// txs accepted by the node
+    pub fn sends(&self) -> u64 {
+        self.sent.load(Relaxed)
+    }
+
+    /// targets done with sending, accepted or not
+    pub fn attempts(&self) -> u64 {
+        self.sent.load(Relaxed) + self.unsent.load(Relaxed)
+    }
+
+    /// txs included and succeeded
+    pub fn succeeded(&self) -> u64 {
+        self.succeed.load(Relaxed)
+    }
+
+    /// targets failed to send, reverted or without a receipt
+    pub fn failures(&self) -> u64 {
+        self.failed.load(Relaxed)
+    }
+
+    pub fn errors(&self) -> BTreeMap<&'static str, u64> {
+        self.errors.lock().unwrap().clone()
+    }
+
+    /// sent and succeeded txs of every target
+    pub fn targets(&self) -> BTreeMap<Address, (u64, u64)> {
+        self.targets.lock().unwrap().clone()
+    }
+}
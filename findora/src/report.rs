@@ -0,0 +1,335 @@
+use crate::{error::Result, latency::LatencySummary};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// file names inside a run directory
+pub const PARAMS_FILE: &str = "params.json";
+pub const NETWORK_FILE: &str = "network.json";
+pub const METRICS_FILE: &str = "metrics.json";
+pub const SUMMARY_FILE: &str = "summary.txt";
+pub const RESULT_FILE: &str = "result.json";
+pub const ERRORS_FILE: &str = "errors.json";
+
+/// smallest change of latency counted as a regression, in the unit of the latency
+const MIN_LATENCY_DELTA: f64 = 1.0;
+/// smallest change of an error rate counted as a regression, percent of sends
+const MIN_ERROR_DELTA: f64 = 0.1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunParams {
+    /// the raw command line
+    pub args: Vec<String>,
+    /// the parsed command
+    pub command: String,
+}
+
+/// A distinct error message seen in a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorSample {
+    pub category: String,
+    pub message: String,
+    pub count: u64,
+}
+
+/// Requests of one kind in a mixed workload
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct KindResult {
+    pub total: u64,
+    pub succeed: u64,
+    /// response time of the succeeded requests, milliseconds
+    pub latency: LatencySummary,
+}
+
+impl KindResult {
+    pub fn success_ratio(&self) -> f64 {
+        if self.total > 0 {
+            self.succeed as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The machine-readable result of a test run
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RunResult {
+    pub total: u64,
+    pub succeed: u64,
+    pub elapsed_secs: f64,
+    pub start_height: u64,
+    pub end_height: u64,
+    /// latency percentiles by name, milliseconds
+    pub latency: BTreeMap<String, LatencySummary>,
+    /// error counts by category
+    pub errors: BTreeMap<String, u64>,
+    /// requests by kind
+    #[serde(default)]
+    pub kinds: BTreeMap<String, KindResult>,
+    /// why the run stopped early
+    #[serde(default)]
+    pub aborted: Option<String>,
+}
+
+impl RunResult {
+    /// load from a run directory or a result file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = if path.is_dir() {
+            path.join(RESULT_FILE)
+        } else {
+            path.to_path_buf()
+        };
+        let data = std::fs::read_to_string(file)?;
+        Ok(serde_json::from_str(data.as_str())?)
+    }
+
+    pub fn tps(&self) -> f64 {
+        if self.elapsed_secs > 0.0 {
+            self.succeed as f64 / self.elapsed_secs
+        } else {
+            0.0
+        }
+    }
+
+    pub fn success_ratio(&self) -> f64 {
+        if self.total > 0 {
+            self.succeed as f64 / self.total as f64
+        } else {
+            0.0
+        }
+    }
+
+    /// errors of `category` in percent of all sends
+    pub fn error_rate(&self, category: &str) -> f64 {
+        if self.total > 0 {
+            self.errors.get(category).copied().unwrap_or_default() as f64 / self.total as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    /// compare with a `base` run, a change is a regression if it gets worse by more than `threshold` percent
+    pub fn compare(&self, base: &RunResult, threshold: f64) -> Vec<Change> {
+        let mut changes = vec![
+            Change::new("tps", base.tps(), self.tps(), true, threshold, 0.0),
+            Change::new(
+                "success_ratio",
+                base.success_ratio(),
+                self.success_ratio(),
+                true,
+                threshold,
+                0.0,
+            ),
+        ];
+        base.latency.iter().for_each(|(name, b)| {
+            let c = self.latency.get(name).cloned().unwrap_or_default();
+            [("p50", b.p50, c.p50), ("p90", b.p90, c.p90), ("p99", b.p99, c.p99)]
+                .iter()
+                .for_each(|(p, b, c)| {
+                    let name = format!("latency.{}.{}", name, p);
+                    changes.push(Change::new(
+                        name,
+                        *b as f64,
+                        *c as f64,
+                        false,
+                        threshold,
+                        MIN_LATENCY_DELTA,
+                    ));
+                });
+        });
+        base.kinds.iter().for_each(|(name, b)| {
+            let c = self.kinds.get(name).cloned().unwrap_or_default();
+            let ratio = format!("kinds.{}.success_ratio", name);
+            changes.push(Change::new(
+                ratio,
+                b.success_ratio(),
+                c.success_ratio(),
+                true,
+                threshold,
+                0.0,
+            ));
+            let p99 = format!("kinds.{}.p99", name);
+            changes.push(Change::new(
+                p99,
+                b.latency.p99 as f64,
+                c.latency.p99 as f64,
+                false,
+                threshold,
+                MIN_LATENCY_DELTA,
+            ));
+        });
+        let categories = base.errors.keys().chain(self.errors.keys()).collect::<BTreeSet<_>>();
+        categories.into_iter().for_each(|category| {
+            let name = format!("errors.{}.rate", category);
+            changes.push(Change::new(
+                name,
+                base.error_rate(category),
+                self.error_rate(category),
+                false,
+                threshold,
+                MIN_ERROR_DELTA,
+            ));
+        });
+        changes
+    }
+}
+
+/// A metric of two runs
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub name: String,
+    pub base: f64,
+    pub current: f64,
+    pub regressed: bool,
+}
+
+impl Change {
+    /// a regression gets worse by more than `threshold` percent and by more than `min_delta`, the
+    /// latter alone decides when the base is zero
+    fn new<S: Into<String>>(
+        name: S,
+        base: f64,
+        current: f64,
+        higher_is_better: bool,
+        threshold: f64,
+        min_delta: f64,
+    ) -> Self {
+        let mut change = Self {
+            name: name.into(),
+            base,
+            current,
+            regressed: false,
+        };
+        let percent = change.percent();
+        let (worse, percent) = if higher_is_better {
+            (base - current, -percent)
+        } else {
+            (current - base, percent)
+        };
+        change.regressed = worse > min_delta && (base == 0.0 || percent > threshold);
+        change
+    }
+
+    /// relative change in percent, infinite if the base is zero
+    pub fn percent(&self) -> f64 {
+        if self.base == self.current {
+            0.0
+        } else if self.base == 0.0 {
+            f64::INFINITY.copysign(self.current)
+        } else {
+            (self.current - self.base) / self.base.abs() * 100.0
+        }
+    }
+}
+
+/// A directory keeping everything of one fund or test run
+#[derive(Debug, Clone)]
+pub struct RunDir {
+    path: PathBuf,
+}
+
+impl RunDir {
+    /// create `<root>/<kind>-<timestamp>` and save the command line into it
+    pub fn create<P: AsRef<Path>>(root: P, kind: &str, command: String) -> Result<Self> {
+        let name = format!("{}-{}", kind, chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let path = root.as_ref().join(name);
+        std::fs::create_dir_all(&path)?;
+        let run = Self { path };
+        let params = RunParams {
+            args: std::env::args().collect(),
+            command,
+        };
+        run.save(PARAMS_FILE, &params)?;
+        Ok(run)
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// save `data` as a json file
+    pub fn save<T: Serialize + ?Sized>(&self, name: &str, data: &T) -> Result<()> {
+        let data = serde_json::to_vec_pretty(data)?;
+        std::fs::write(self.path.join(name), data)?;
+        Ok(())
+    }
+
+    /// append summary lines
+    pub fn save_summary(&self, lines: &[String]) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.join(SUMMARY_FILE))?;
+        lines.iter().try_for_each(|line| writeln!(file, "{}", line))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(total: u64, succeed: u64, errors: &[(&str, u64)]) -> RunResult {
+        RunResult {
+            total,
+            succeed,
+            elapsed_secs: 10.0,
+            errors: errors.iter().map(|(c, n)| (c.to_string(), *n)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn change<'a>(changes: &'a [Change], name: &str) -> &'a Change {
+        changes.iter().find(|c| c.name == name).unwrap()
+    }
+
+    #[test]
+    fn errors_are_compared_by_rate() {
+        let base = run(1000, 990, &[("timeout", 10)]);
+        let current = run(2000, 1980, &[("timeout", 20)]);
+        let changes = current.compare(&base, 10.0);
+        let timeout = change(&changes, "errors.timeout.rate");
+        assert_eq!(timeout.base, 1.0);
+        assert_eq!(timeout.current, 1.0);
+        assert!(!timeout.regressed);
+
+        let current = run(1000, 980, &[("timeout", 20)]);
+        assert!(change(&current.compare(&base, 10.0), "errors.timeout.rate").regressed);
+    }
+
+    #[test]
+    fn new_error_category_needs_min_delta() {
+        let base = run(10000, 10000, &[]);
+        let current = run(10000, 9999, &[("nonce", 1)]);
+        let changes = current.compare(&base, 10.0);
+        let nonce = change(&changes, "errors.nonce.rate");
+        assert!(nonce.percent().is_infinite());
+        assert!(!nonce.regressed);
+
+        let current = run(10000, 9900, &[("nonce", 100)]);
+        assert!(change(&current.compare(&base, 10.0), "errors.nonce.rate").regressed);
+    }
+
+    #[test]
+    fn tps_regression_by_threshold() {
+        let base = run(1000, 1000, &[]);
+        assert!(!change(&run(1000, 950, &[]).compare(&base, 10.0), "tps").regressed);
+        assert!(change(&run(1000, 850, &[]).compare(&base, 10.0), "tps").regressed);
+        assert!(!change(&run(1000, 1000, &[]).compare(&run(0, 0, &[]), 10.0), "tps").regressed);
+    }
+
+    #[test]
+    fn latency_from_zero_base() {
+        let mut base = run(1000, 1000, &[]);
+        base.latency.insert("ack".to_string(), LatencySummary::default());
+        let mut current = base.clone();
+        current.latency.get_mut("ack").unwrap().p50 = 1;
+        assert!(!change(&current.compare(&base, 10.0), "latency.ack.p50").regressed);
+        current.latency.get_mut("ack").unwrap().p50 = 5;
+        assert!(change(&current.compare(&base, 10.0), "latency.ack.p50").regressed);
+    }
+}
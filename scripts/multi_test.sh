@@ -19,7 +19,7 @@ run_one_test() {
 
     {
         echo "$endpoint $count $concurrency $timeout"
-        RUST_LOG=info ./feth --network "$endpoint" --source "$source" --count "$count" --max-parallelism "$concurrency" --timeout "$timeout" 2>&1
+        RUST_LOG=info ./feth test --network "$endpoint" --mode basic --source "$source" --count "$count" --max-threads "$concurrency" --timeout "$timeout" 2>&1
         echo "wait for $waittime seconds to make sure all previous txns have been finished..."
     } >>"$logfile"
     sleep "$waittime"
@@ -9,55 +9,75 @@ cargo build --release
 echo "4c10030f9fc32db7bbf15e8b527823a4083ab36d7b525b98e6a3f01d875960cc" >.secret
 
 # Generate some accounts and transfer some Ethers (for example 1000*0.1 ether) to them
-cargo run --release -- fund --network qa01 --count 100 --amount 1000
+cargo run --release -- fund --network qa,01 --count 100 --amount 1000
 
 # The accounts used for testing will be saved to "sources_keys.001"
 less sources_keys.001
 
+# Or derive the accounts from one seed mnemonic at m/44'/60'/0'/0/i, a new mnemonic is saved to ".mnemonic" if missing
+# "source_keys.001" only keeps the seed file and the range, keep ".mnemonic" as safe as ".secret"
+cargo run --release -- fund --network qa,01 --count 10000 --amount 10 --seed .mnemonic
+# Run it again for the next 10000 accounts of the mnemonic, the previous set is moved to ".source_keys.001.bak"
+
 # re-deposit the account whose balance is lower than the specified amount
-cargo run --release -- fund --network qa01 --amount 1000 --load --redeposit --timeout 5
+cargo run --release -- fund --network qa,01 --amount 1000 --load --redeposit --timeout 5
 
 # Add more accounts and re-deposit them
-cargo run --release -- fund --network qa01 --count 200 --amount 1000 --load --redeposit
+cargo run --release -- fund --network qa,01 --count 200 --amount 1000 --load --redeposit
 
 # Starting tests
-cargo run --release -- --network qa01 --count 10 --max-parallism 200 --timeout 10
+cargo run --release -- test --network qa,01 --mode basic --count 10 --max-threads 200 --timeout 10
 # 1. Load source accounts from "source_keys.001"
 # 2. Filter out account which doesn't have sufficient balance
 # 3. Generate the "count" of new addresses per source account to receive Ethers
-# 4. Create a thread pool with size of "max-parallelism", thread pool size could be larger than source keys' count
+# 4. Create a thread pool with size of "max-threads", thread pool size could be larger than source keys' count
 # 5. Build transactions and send them to the endpoints
 #   a. One context for each source account
 #   b. For one source account, we will build tx, sign it, then send it, and wait for the receipt, 3*block_time maximum.
-#   c. All test results will save to files with prefix "metric.target.*"
+#   c. All test results will save to "runs/test-<timestamp>", see "--output"
+#      params.json, network.json, metrics.json and summary.txt
 
 # Collect test results for further analysis
 mkdir test_results
-mv metric.target.* test_results
-mv metric.001 test_results
+mv runs/* test_results
 
 # Backup your source_keys file
 cp source_keys.001 ~/source_keys.xx.200
 
 # Specify source_keys file
-cargo run --release -- --network qa01 --count 10 --max-parallelism 200 --source ~/source_keys.xx.200
+cargo run --release -- test --network qa,01 --mode basic --count 10 --max-threads 200 --source ~/source_keys.xx.200
 
 # Multi endpoints seperated by comma
 # The source account will be divided equally to each endpoint
-cargo run --release -- --network http://localhost:8545,http://localhost:8555 --count 10 --max-parallelism 200
+cargo run --release -- test --network http://localhost:8545,http://localhost:8555 --mode basic --count 10 --max-threads 200
+
+# Distributed tests, start a worker on every load host of a trusted network, jobs aren't authenticated,
+# with a copy of the mnemonic a key set is sent as its range only
+cargo run --release -- worker --listen 10.0.0.1:7878 --seed ~/seed.txt
+# then split the source keys among the workers, their results are merged into "runs/coordinate-<timestamp>"
+cargo run --release -- coordinate --network qa,01 --count 10 --source ~/source_keys.xx.200 --workers 10.0.0.1:7878,10.0.0.2:7878
+
+# Watch a test on a live dashboard, the logs go to "feth.log"
+cargo run --release -- test --network qa,01 --mode basic --count 10 --source ~/source_keys.xx.200 --tui
+
+# Describe a test in a TOML or YAML scenario file, flags override its fields
+cargo run --release -- test --scenario scenarios/qa01-soak.toml --duration 600
+
+# Drain the source accounts back to the faucet account when tests are done, or to "--to <ADDRESS>"
+cargo run --release -- sweep --network qa,01 --source ~/source_keys.xx.200
 
 # Retrieve transaction by hash
-cargo run --release -- transaction --network qa01 --hash 0x1d44bd3fc1764c6dfadb6eef7191cf44a81607c02c41255f7802f4779de55dcf
+cargo run --release -- transaction --network qa,01 --hash 0x1d44bd3fc1764c6dfadb6eef7191cf44a81607c02c41255f7802f4779de55dcf
 
 # Retrieve account basic information, such as `balance` and `nonce`
-cargo run --release -- info --network qa01 --account 0x512a4d5e8478D11682925b29705F6c8d6AE9e39d
+cargo run --release -- info --network qa,01 --account 0x512a4d5e8478D11682925b29705F6c8d6AE9e39d
 
 # Retrieve block information, such as height, timestamp, txn counts, time interval
 # show current block information
-cargo run --release -- block --network qa01
+cargo run --release -- block --network qa,01
 # 11297,1646890818,0,16
 # Show evm blocks since specified height
-cargo run --release -- block --network qa01 --start 11290
+cargo run --release -- block --network qa,01 --start 11290
 # 11290,1646890708,0,16
 # 11291,1646890724,0,16
 # 11292,1646890740,0,16